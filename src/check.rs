@@ -0,0 +1,239 @@
+use crate::{
+    env::{Env as Environment, EnvVec},
+    expr::{Expr, Input, Pattern, Statement},
+};
+
+/// Types of variables in scope, where `None` means the type is not known
+type Env = EnvVec<String, Option<Type>>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Type {
+    Int,
+    Float,
+    Tag,
+    Tuple,
+    Fn,
+}
+
+impl Type {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "Int" => Some(Self::Int),
+            "Float" => Some(Self::Float),
+            "Tag" => Some(Self::Tag),
+            "Tuple" => Some(Self::Tuple),
+            "Fn" => Some(Self::Fn),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum TypeErrorKind {
+    /// An annotation does not name a type
+    UnknownType,
+    /// An annotated expression has a different type than its annotation
+    Mismatch { expected: Type, found: Type },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TypeError<'a> {
+    pub(crate) kind: TypeErrorKind,
+    pub(crate) span: Input<'a>,
+}
+
+/// Check the type annotations on assignments within `e`
+///
+/// The language is dynamically typed, so only expressions whose type is evident from the source
+/// are checked: literals, tuples, functions, and variables bound to those.
+pub(crate) fn check<'a>(e: &Expr<'a>) -> Result<(), TypeError<'a>> {
+    expr(e, &mut Env::new())
+}
+
+fn expr<'a>(e: &Expr<'a>, env: &mut Env) -> Result<(), TypeError<'a>> {
+    match e {
+        Expr::Int(_) | Expr::Float(_) | Expr::Tag(..) | Expr::Id(_) | Expr::Expand(_) => Ok(()),
+        Expr::Tuple(_, xs) => xs.iter().try_for_each(|x| expr(x, env)),
+        Expr::App(app) => {
+            expr(&app.inner, env)?;
+            app.args.iter().try_for_each(|x| expr(x, env))
+        }
+        Expr::Case(case) => {
+            expr(&case.subject, env)?;
+            for arm in &case.arms {
+                env.push();
+                forget(&arm.pattern, env);
+                let result = expr(&arm.expr, env);
+                env.pop();
+                result?;
+            }
+            match &case.default {
+                Some(default) => expr(&default.expr, env),
+                None => Ok(()),
+            }
+        }
+        Expr::Paren(_, inner) => expr(inner, env),
+        Expr::Do(do_struct) => {
+            env.push();
+            let result = block(&do_struct.statements, do_struct.ret.as_deref(), env);
+            env.pop();
+            result
+        }
+        Expr::Fn(_, param, body) => {
+            env.push();
+            env.insert(param.as_inner().to_string(), None);
+            let result = expr(body, env);
+            env.pop();
+            result
+        }
+    }
+}
+
+fn block<'a>(
+    statements: &[Statement<'a>],
+    ret: Option<&Expr<'a>>,
+    env: &mut Env,
+) -> Result<(), TypeError<'a>> {
+    for statement in statements {
+        match statement {
+            Statement::Expr(e) => expr(e, env)?,
+            Statement::Assign(assign) => {
+                expr(&assign.expr, env)?;
+                let found = infer(&assign.expr, env);
+                let expected = match assign.ty {
+                    Some(name) => Some(Type::from_name(name.as_inner()).ok_or(TypeError {
+                        kind: TypeErrorKind::UnknownType,
+                        span: name,
+                    })?),
+                    None => None,
+                };
+                if let (Some(expected), Some(found)) = (expected, found) {
+                    if expected != found {
+                        return Err(TypeError {
+                            kind: TypeErrorKind::Mismatch { expected, found },
+                            span: assign.expr.span(),
+                        });
+                    }
+                }
+                match &assign.pattern {
+                    Pattern::Id(id) => env.insert(id.as_inner().to_string(), expected.or(found)),
+                    pattern => forget(pattern, env),
+                }
+            }
+        }
+    }
+    match ret {
+        Some(e) => expr(e, env),
+        None => Ok(()),
+    }
+}
+
+/// The type of `e`, if it is evident from the source
+fn infer(e: &Expr, env: &Env) -> Option<Type> {
+    match e {
+        Expr::Int(_) => Some(Type::Int),
+        Expr::Float(_) => Some(Type::Float),
+        Expr::Tag(..) => Some(Type::Tag),
+        Expr::Id(id) => env.get(id.as_inner()).copied().flatten(),
+        Expr::Tuple(..) => Some(Type::Tuple),
+        Expr::Fn(..) => Some(Type::Fn),
+        Expr::Paren(_, inner) => infer(inner, env),
+        Expr::Expand(_) | Expr::App(_) | Expr::Case(_) | Expr::Do(_) => None,
+    }
+}
+
+/// Shadow every variable bound by `pattern` with an unknown type
+fn forget(pattern: &Pattern, env: &mut Env) {
+    match pattern {
+        Pattern::Id(id) => env.insert(id.as_inner().to_string(), None),
+        Pattern::Collect(ellipsis) => {
+            if let Some(id) = ellipsis.id {
+                env.insert(id.as_inner().to_string(), None);
+            }
+        }
+        Pattern::Tuple(_, xs) => xs.iter().for_each(|x| forget(x, env)),
+        Pattern::App(app) => {
+            forget(&app.f, env);
+            app.xs.iter().for_each(|x| forget(x, env));
+        }
+        Pattern::Paren(_, inner) => forget(inner, env),
+        Pattern::Or(_, xs) => xs.iter().for_each(|x| forget(x, env)),
+        Pattern::Ignore(_) | Pattern::Int(_) | Pattern::Tag(..) => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::parse_expr;
+
+    /// The error kind and the source it points at
+    fn check_str(s: &str) -> Result<(), (TypeErrorKind, &str)> {
+        check(&parse_expr(s).unwrap()).map_err(|e| (e.kind, e.span.as_inner()))
+    }
+
+    #[test]
+    fn test_annotation_ok() {
+        assert_eq!(check_str("{ x : Int = 1; x }"), Ok(()));
+        assert_eq!(check_str("{ x : Float = 1.5; x }"), Ok(()));
+        assert_eq!(check_str("{ x : Tuple = (1, 2); x }"), Ok(()));
+        assert_eq!(check_str("{ f : Fn = x -> x; f(1) }"), Ok(()));
+        assert_eq!(check_str("{ x : Tag = f(1); x }"), Ok(()));
+        assert_eq!(check_str("{ x = 1; y : Int = x; y }"), Ok(()));
+    }
+
+    #[test]
+    fn test_annotation_mismatch() {
+        assert_eq!(
+            check_str("{ x : Tag = 1; x }"),
+            Err((
+                TypeErrorKind::Mismatch {
+                    expected: Type::Tag,
+                    found: Type::Int
+                },
+                "1"
+            )),
+        );
+        assert_eq!(
+            check_str("{ x = (1, 2); y : Int = x; y }"),
+            Err((
+                TypeErrorKind::Mismatch {
+                    expected: Type::Int,
+                    found: Type::Tuple
+                },
+                "x"
+            )),
+        );
+        assert_eq!(
+            check_str("f({ x : Fn = :a; })"),
+            Err((
+                TypeErrorKind::Mismatch {
+                    expected: Type::Fn,
+                    found: Type::Tag
+                },
+                ":a"
+            )),
+        );
+    }
+
+    #[test]
+    fn test_annotation_scope() {
+        // Bindings in arms and parameters shadow outer types
+        assert_eq!(
+            check_str("{ x = 1; f = x -> { y : Tag = x; y }; f(:a) }"),
+            Ok(())
+        );
+        assert_eq!(
+            check_str("{ x = 1; case :a of x = { y : Tag = x; y } end }"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_unknown_type() {
+        assert_eq!(
+            check_str("{ x : Foo = 1; x }"),
+            Err((TypeErrorKind::UnknownType, "Foo"))
+        );
+    }
+}
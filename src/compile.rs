@@ -0,0 +1,570 @@
+use std::collections::HashMap;
+
+use crate::{
+    env::Env as Environment,
+    error::Error,
+    eval::{default_env, float_value, int_value, RuntimeError, RuntimeErrorKind, Value},
+    expr::{Case, Expr, Input, Pattern},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum CompileErrorKind {
+    /// The compiler cannot compile the expression or pattern yet
+    Unsupported,
+}
+
+/// An error found while compiling, before the program is run
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct CompileError<'a> {
+    pub(crate) kind: CompileErrorKind,
+    pub(crate) span: Input<'a>,
+}
+
+/// An instruction for the stack machine run by `run`
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Instr<'a> {
+    /// Push the constant at the given index of the pool
+    PushConst(usize),
+    /// Push the value of a variable
+    Load(&'a str),
+    /// Pop the given number of values and push them as a tuple, first pushed first
+    MakeTuple(usize),
+    /// Pop the given number of arguments, then the function, and push the result of the call
+    Call(usize),
+    /// Discard the top of the stack
+    Pop,
+    /// Continue at the given instruction
+    Jump(usize),
+    /// Continue at the given instruction unless the top of the stack is the given tag, which is
+    /// left on the stack either way
+    JumpIfNotTag(&'a str, usize),
+    /// Pop the top of the stack into a variable, in a new scope
+    Bind(&'a str),
+    /// Drop the scope of the last `Bind`
+    Unbind,
+    /// Fail, as no arm of a case matched its subject
+    NoMatch,
+}
+
+/// A literal as a key into the constant pool, floats compared by their bits
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum Const<'a> {
+    Int(i64),
+    Float(u64),
+    Tag(&'a str),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Program<'a> {
+    /// Literals used by the code, each stored once
+    pub(crate) consts: Vec<Value<'a>>,
+    /// The index of each literal in `consts`
+    const_indices: HashMap<Const<'a>, usize>,
+    pub(crate) code: Vec<Instr<'a>>,
+    /// The source of each instruction, by index, for reporting runtime errors
+    pub(crate) spans: Vec<Input<'a>>,
+}
+
+impl<'a> Program<'a> {
+    fn push(&mut self, instr: Instr<'a>, span: Input<'a>) {
+        self.code.push(instr);
+        self.spans.push(span);
+    }
+
+    /// Push a constant, adding it to the pool unless an equal one is already there
+    fn push_const(&mut self, key: Const<'a>, span: Input<'a>) {
+        let consts = &mut self.consts;
+        let index = *self.const_indices.entry(key).or_insert_with(|| {
+            consts.push(match key {
+                Const::Int(x) => Value::Int(x),
+                Const::Float(bits) => Value::Float(f64::from_bits(bits)),
+                Const::Tag(name) => Value::Tag(name),
+            });
+            consts.len() - 1
+        });
+        self.push(Instr::PushConst(index), span);
+    }
+
+    /// Push a jump whose target is not known yet, returning its index for `patch`
+    fn push_jump(&mut self, instr: Instr<'a>, span: Input<'a>) -> usize {
+        self.push(instr, span);
+        self.code.len() - 1
+    }
+
+    /// Point the jump at `index` to the next instruction to be pushed
+    fn patch(&mut self, index: usize) {
+        let next = self.code.len();
+        match &mut self.code[index] {
+            Instr::Jump(target) | Instr::JumpIfNotTag(_, target) => *target = next,
+            instr => panic!("compiler: patching a non-jump instruction: {instr:?}"),
+        }
+    }
+}
+
+/// Compile an expression to instructions which leave its value on the stack
+///
+/// Only literals, variables, tuples, applications, and `case` on tags and ids are supported so far;
+/// anything else is an `Unsupported` error.
+/// Literals out of range are reported here, as runtime errors as they would be by the evaluator,
+/// rather than when the program is run, even in arms which are never compiled.
+// Only the tests call the compiler until the REPL can run everything through it
+#[allow(dead_code)]
+pub(crate) fn compile<'a>(e: &Expr<'a>) -> Result<Program<'a>, Error<'a>> {
+    e.check_literals()?;
+    let mut program = Program::default();
+    expr(e, &mut program)?;
+    Ok(program)
+}
+
+fn expr<'a>(e: &Expr<'a>, program: &mut Program<'a>) -> Result<(), Error<'a>> {
+    match e {
+        Expr::Int(span) => program.push_const(Const::Int(int_value(*span)?), *span),
+        Expr::Float(span) => program.push_const(Const::Float(float_value(*span)?.to_bits()), *span),
+        Expr::Tag(span, name) => program.push_const(Const::Tag(name.as_inner()), *span),
+        Expr::Id(id) => program.push(Instr::Load(id.as_inner()), *id),
+        Expr::Tuple(span, xs) => {
+            for x in xs {
+                expr(x, program)?;
+            }
+            program.push(Instr::MakeTuple(xs.len()), *span);
+        }
+        Expr::App(app) => {
+            expr(&app.inner, program)?;
+            for arg in &app.args {
+                expr(arg, program)?;
+            }
+            program.push(Instr::Call(app.args.len()), app.span);
+        }
+        Expr::Paren(_, inner) => expr(inner, program)?,
+        Expr::Case(case) => self::case(case, program)?,
+        Expr::Expand(_) | Expr::Do(_) | Expr::Fn(..) => {
+            return Err(Error::Compile(CompileError {
+                kind: CompileErrorKind::Unsupported,
+                span: e.span(),
+            }))
+        }
+    }
+    Ok(())
+}
+
+/// How a case selects an arm, worked out before any code is generated
+///
+/// Patterns are flat so far, so the tree has one level: the subject's tag is tested against each
+/// tag at most once, in the order of the arms, and anything else goes to the fallback. Arms after
+/// the first which always matches, and arms repeating an earlier tag, can never be selected and are
+/// left out.
+struct Decision<'c, 'a> {
+    /// The tag pattern and body of the first arm for each tag
+    tags: Vec<(Input<'a>, &'a str, &'c Expr<'a>)>,
+    fallback: Fallback<'c, 'a>,
+}
+
+/// What a case does when none of the tags match
+enum Fallback<'c, 'a> {
+    /// Evaluate the body of `_` or `of else`, discarding the subject
+    Discard(Input<'a>, &'c Expr<'a>),
+    /// Evaluate the body with the subject bound to the id
+    Bind(Input<'a>, &'c Expr<'a>),
+    /// Fail, as no arm matches
+    NoMatch,
+}
+
+impl<'c, 'a> Decision<'c, 'a> {
+    fn new(case: &'c Case<'a>) -> Result<Self, CompileError<'a>> {
+        let mut tags: Vec<(Input<'a>, &'a str, &'c Expr<'a>)> = Vec::new();
+        for arm in &case.arms {
+            let mut pattern = &arm.pattern;
+            while let Pattern::Paren(_, inner) = pattern {
+                pattern = inner;
+            }
+            let fallback = match pattern {
+                Pattern::Tag(span, name) => {
+                    if !tags.iter().any(|(_, tag, _)| *tag == name.as_inner()) {
+                        tags.push((*span, name.as_inner(), &arm.expr));
+                    }
+                    continue;
+                }
+                Pattern::Ignore(span) => Fallback::Discard(*span, &arm.expr),
+                Pattern::Id(id) => Fallback::Bind(*id, &arm.expr),
+                pattern => {
+                    return Err(CompileError {
+                        kind: CompileErrorKind::Unsupported,
+                        span: pattern.span(),
+                    })
+                }
+            };
+            return Ok(Self { tags, fallback });
+        }
+        let fallback = match &case.default {
+            Some(default) => Fallback::Discard(default.span, &default.expr),
+            None => Fallback::NoMatch,
+        };
+        Ok(Self { tags, fallback })
+    }
+}
+
+/// Compile a case into a test for each tag, each jumping to the next test on failure
+///
+/// ```text
+///     subject
+///     JumpIfNotTag(:a, next)
+///     Pop
+///     body
+///     Jump(end)
+/// next:
+///     ...
+///     fallback
+/// end:
+/// ```
+///
+/// Only tag, id, and `_` patterns are supported so far; anything else is an `Unsupported` error.
+fn case<'a>(case: &Case<'a>, program: &mut Program<'a>) -> Result<(), Error<'a>> {
+    let decision = Decision::new(case).map_err(Error::Compile)?;
+    expr(&case.subject, program)?;
+    let mut ends = Vec::new();
+    for (span, tag, body) in decision.tags {
+        let next = program.push_jump(Instr::JumpIfNotTag(tag, 0), span);
+        program.push(Instr::Pop, span);
+        expr(body, program)?;
+        ends.push(program.push_jump(Instr::Jump(0), span));
+        program.patch(next);
+    }
+    match decision.fallback {
+        Fallback::Discard(span, body) => {
+            program.push(Instr::Pop, span);
+            expr(body, program)?;
+        }
+        Fallback::Bind(id, body) => {
+            program.push(Instr::Bind(id.as_inner()), id);
+            expr(body, program)?;
+            program.push(Instr::Unbind, id);
+        }
+        Fallback::NoMatch => program.push(Instr::NoMatch, case.span),
+    }
+    for end in ends {
+        program.patch(end);
+    }
+    Ok(())
+}
+
+/// Run a compiled program with the builtins of the default environment
+///
+/// Errors without a span are reported at the span of the instruction that raised them.
+// Only the tests run compiled programs until the REPL can run everything through the compiler
+#[allow(dead_code)]
+pub(crate) fn run<'a>(program: &Program<'a>) -> Result<Value<'a>, RuntimeError<'a>> {
+    let mut env = default_env();
+    let mut stack = Vec::new();
+    let mut pc = 0;
+    while let Some(instr) = program.code.get(pc) {
+        let span = program.spans[pc];
+        pc += 1;
+        match instr {
+            Instr::PushConst(index) => stack.push(program.consts[*index].clone()),
+            Instr::Load(name) => match env.get(*name) {
+                Some(value) => stack.push(value.borrow().clone()),
+                None => return Err(RuntimeError::new(RuntimeErrorKind::Unbound, span)),
+            },
+            Instr::MakeTuple(len) => {
+                let xs = stack.split_off(stack.len() - len);
+                stack.push(Value::Tuple(xs.into_iter().map(Value::into_ptr).collect()));
+            }
+            Instr::Call(len) => {
+                let args = stack.split_off(stack.len() - len);
+                let value = match stack.pop() {
+                    Some(Value::Builtin(builtin)) => {
+                        if args.len() != builtin.arity {
+                            return Err(RuntimeError::new(
+                                RuntimeErrorKind::Arity {
+                                    expected: builtin.arity,
+                                    found: args.len(),
+                                },
+                                span,
+                            ));
+                        }
+                        (builtin.f)(&args).map_err(|err| err.or_at(span))?
+                    }
+                    _ => return Err(RuntimeError::new(RuntimeErrorKind::NotCallable, span)),
+                };
+                stack.push(value);
+            }
+            Instr::Pop => {
+                stack.pop();
+            }
+            Instr::Jump(target) => pc = *target,
+            Instr::JumpIfNotTag(name, target) => match stack.last() {
+                Some(Value::Tag(tag)) if tag == name => {}
+                _ => pc = *target,
+            },
+            Instr::Bind(name) => {
+                let value = stack.pop().expect("vm: nothing to bind");
+                env.push();
+                env.insert(name.to_string(), value.into_ptr());
+            }
+            Instr::Unbind => env.pop(),
+            Instr::NoMatch => return Err(RuntimeError::new(RuntimeErrorKind::NoMatch, span)),
+        }
+    }
+    Ok(stack.pop().expect("vm: program left no value"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::parse_expr;
+
+    fn compile_run(s: &str) -> Result<Value<'_>, RuntimeError<'_>> {
+        run(&compile(&parse_expr(s).unwrap()).unwrap())
+    }
+
+    /// The source of the `Unsupported` error from compiling `s`
+    fn unsupported(s: &str) -> &str {
+        match compile(&parse_expr(s).unwrap()) {
+            Err(Error::Compile(CompileError {
+                kind: CompileErrorKind::Unsupported,
+                span,
+            })) => span.as_inner(),
+            result => panic!("expected an unsupported error: {result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_compile() {
+        let s = "add(2, (3))";
+        let program = compile(&parse_expr(s).unwrap()).unwrap();
+        assert_eq!(program.consts, vec![Value::Int(2), Value::Int(3)]);
+        assert_eq!(
+            program.code,
+            vec![
+                Instr::Load("add"),
+                Instr::PushConst(0),
+                Instr::PushConst(1),
+                Instr::Call(2),
+            ]
+        );
+        assert_eq!(
+            program.spans,
+            vec![
+                Input::new(s, 0, 3),
+                Input::new(s, 4, 5),
+                Input::new(s, 8, 9),
+                Input::new(s, 0, 11),
+            ]
+        );
+        let program = compile(&parse_expr("0x1.8p3").unwrap()).unwrap();
+        assert_eq!(program.consts, vec![Value::Float(12.0)]);
+        assert_eq!(program.code, vec![Instr::PushConst(0)]);
+    }
+
+    #[test]
+    fn test_const_pool() {
+        let program = compile(&parse_expr("(1234, 1234)").unwrap()).unwrap();
+        assert_eq!(program.consts, vec![Value::Int(1234)]);
+        assert_eq!(
+            program.code,
+            vec![
+                Instr::PushConst(0),
+                Instr::PushConst(0),
+                Instr::MakeTuple(2),
+            ]
+        );
+
+        // Equal values are shared however they are spelled
+        let program = compile(&parse_expr("f(:a, 1_000, : a, 1000u16, :b)").unwrap()).unwrap();
+        assert_eq!(
+            program.consts,
+            vec![Value::Tag("a"), Value::Int(1000), Value::Tag("b")]
+        );
+        assert_eq!(&program.code[1..6], [0, 1, 0, 1, 2].map(Instr::PushConst));
+        let program = compile(&parse_expr("(0x1.8p3, 12.0)").unwrap()).unwrap();
+        assert_eq!(program.consts, vec![Value::Float(12.0)]);
+    }
+
+    #[test]
+    fn test_run_app() {
+        assert_eq!(compile_run("add(2, 3)"), Ok(Value::Int(5)));
+        assert_eq!(compile_run("add(add(1, 2), div(9, 3))"), Ok(Value::Int(6)));
+        let s = "add(1)";
+        assert_eq!(
+            compile_run(s),
+            Err(RuntimeError::new(
+                RuntimeErrorKind::Arity {
+                    expected: 2,
+                    found: 1
+                },
+                Input::from(s),
+            ))
+        );
+    }
+
+    #[test]
+    fn test_unsupported() {
+        for (s, span) in [
+            ("x -> x", "x -> x"),
+            ("(1, { x })", "{ x }"),
+            ("f(..xs)", "..xs"),
+        ] {
+            assert_eq!(unsupported(s), span, "{s}");
+        }
+    }
+
+    #[test]
+    fn test_run_unbound() {
+        let s = "add(1, x)";
+        assert_eq!(
+            compile_run(s),
+            Err(RuntimeError::new(
+                RuntimeErrorKind::Unbound,
+                Input::new(s, 7, 8)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_run_not_callable() {
+        let s = "(1, :f(2))";
+        assert_eq!(
+            compile_run(s),
+            Err(RuntimeError::new(
+                RuntimeErrorKind::NotCallable,
+                Input::new(s, 4, 9)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_run_error_span() {
+        let s = "(1, add(2, div(1, 0)))";
+        assert_eq!(
+            compile_run(s),
+            Err(RuntimeError::new(
+                RuntimeErrorKind::DivByZero,
+                Input::new(s, 11, 20)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_compile_case() {
+        let program = compile(&parse_expr("case :b of :a = 1 of :b = 2 end").unwrap()).unwrap();
+        assert_eq!(
+            program.consts,
+            vec![Value::Tag("b"), Value::Int(1), Value::Int(2)]
+        );
+        assert_eq!(
+            program.code,
+            vec![
+                Instr::PushConst(0),
+                Instr::JumpIfNotTag("a", 5),
+                Instr::Pop,
+                Instr::PushConst(1),
+                Instr::Jump(10),
+                Instr::JumpIfNotTag("b", 9),
+                Instr::Pop,
+                Instr::PushConst(2),
+                Instr::Jump(10),
+                Instr::NoMatch,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_case() {
+        for (subject, value) in [(":a", 1), (":b", 2)] {
+            let s = format!("case {subject} of :a = 1 of :b = 2 end");
+            assert_eq!(compile_run(&s), Ok(Value::Int(value)), "{s}");
+        }
+        for (subject, value) in [(":a", 1), (":b", 3), ("4", 3)] {
+            let s = format!("case {subject} of :a = 1 of else = 3 end");
+            assert_eq!(compile_run(&s), Ok(Value::Int(value)), "{s}");
+        }
+        assert_eq!(
+            compile_run("case add(1, 2) of :a = 0 of x = add(x, x) end"),
+            Ok(Value::Int(6))
+        );
+        assert_eq!(
+            compile_run("(case :c of :a = 0 of _c = 1 end, case :a of (:a) = 2 end)"),
+            Ok(Value::Tuple(vec![
+                Value::Int(1).into_ptr(),
+                Value::Int(2).into_ptr()
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_compile_case_unreachable() {
+        // Repeated tags and arms after `x` are never compiled
+        let s = "case :a of :a = 1 of :a = 2 of x = x of :b = 3 of 4 = 5 end";
+        let program = compile(&parse_expr(s).unwrap()).unwrap();
+        assert_eq!(program.consts, vec![Value::Tag("a"), Value::Int(1)]);
+        assert_eq!(
+            program.code,
+            vec![
+                Instr::PushConst(0),
+                Instr::JumpIfNotTag("a", 5),
+                Instr::Pop,
+                Instr::PushConst(1),
+                Instr::Jump(8),
+                Instr::Bind("x"),
+                Instr::Load("x"),
+                Instr::Unbind,
+            ]
+        );
+
+        // Likewise the default after `_`
+        let program = compile(&parse_expr("case :a of _ = 1 of else = 2 end").unwrap()).unwrap();
+        assert_eq!(
+            program.code,
+            vec![Instr::PushConst(0), Instr::Pop, Instr::PushConst(1)]
+        );
+    }
+
+    #[test]
+    fn test_compile_case_unsupported() {
+        for (s, span) in [
+            ("case 1 of 1 = 2 end", "1"),
+            ("case :a of :b = 1 of (x, y) = 2 end", "x, y"),
+            ("case :a of (:some(x)) = x end", ":some(x)"),
+            ("case :a of :a | :b = 1 end", ":a | :b"),
+        ] {
+            assert_eq!(unsupported(s), span, "{s}");
+        }
+    }
+
+    #[test]
+    fn test_run_case_no_match() {
+        let s = "(1, case :c of :a = 1 of :b = 2 end)";
+        assert_eq!(
+            compile_run(s),
+            Err(RuntimeError::new(
+                RuntimeErrorKind::NoMatch,
+                Input::new(s, 4, 35)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_compile_overflow() {
+        // The second arm is never compiled, as the first always matches
+        let s = "case :a of x = 1 of _ = 256u8 end";
+        assert_eq!(
+            compile(&parse_expr(s).unwrap()),
+            Err(Error::Runtime(RuntimeError::new(
+                RuntimeErrorKind::Overflow,
+                Input::new(s, 24, 29)
+            )))
+        );
+    }
+
+    #[test]
+    fn test_run_tuple() {
+        assert_eq!(
+            compile_run("1, (:a, add(1, 1)), ()"),
+            Ok(Value::Tuple(vec![
+                Value::Int(1).into_ptr(),
+                Value::Tuple(vec![Value::Tag("a").into_ptr(), Value::Int(2).into_ptr()]).into_ptr(),
+                Value::Tuple(vec![]).into_ptr(),
+            ]))
+        );
+    }
+}
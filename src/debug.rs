@@ -0,0 +1,252 @@
+use crate::expr::{
+    App, Arm, Assign, Case, DefaultArm, Do, Ellipsis, Expr, Input, Pattern, PatternApp, Statement,
+};
+use std::fmt::{Debug, Formatter, Result};
+
+/// Debug formatting that prints spans as `line:col..line:col` instead of the
+/// source they point into. The derived `Debug` impls are left untouched.
+pub(crate) trait DebugSource {
+    fn fmt_source(&self, fmt: &mut Formatter<'_>) -> Result;
+
+    fn debug_source(&self) -> WithSource<'_, Self> {
+        WithSource(self)
+    }
+}
+
+/// Adapter implementing `Debug` in terms of `DebugSource`
+pub(crate) struct WithSource<'e, T: ?Sized>(&'e T);
+
+impl<'e, T: DebugSource + ?Sized> Debug for WithSource<'e, T> {
+    fn fmt(&self, fmt: &mut Formatter<'_>) -> Result {
+        self.0.fmt_source(fmt)
+    }
+}
+
+impl DebugSource for Input<'_> {
+    fn fmt_source(&self, fmt: &mut Formatter<'_>) -> Result {
+        let (l1, c1) = self.line_col();
+        let (l2, c2) = self.end_line_col();
+        write!(fmt, "{l1}:{c1}..{l2}:{c2}")
+    }
+}
+
+impl<T: DebugSource> DebugSource for Box<T> {
+    fn fmt_source(&self, fmt: &mut Formatter<'_>) -> Result {
+        (**self).fmt_source(fmt)
+    }
+}
+
+impl<T: DebugSource> DebugSource for Option<T> {
+    fn fmt_source(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            Some(x) => fmt.debug_tuple("Some").field(&x.debug_source()).finish(),
+            None => fmt.write_str("None"),
+        }
+    }
+}
+
+impl<T: DebugSource> DebugSource for Vec<T> {
+    fn fmt_source(&self, fmt: &mut Formatter<'_>) -> Result {
+        fmt.debug_list()
+            .entries(self.iter().map(DebugSource::debug_source))
+            .finish()
+    }
+}
+
+impl DebugSource for Ellipsis<'_> {
+    fn fmt_source(&self, fmt: &mut Formatter<'_>) -> Result {
+        fmt.debug_struct("Ellipsis")
+            .field("span", &self.span.debug_source())
+            .field("id", &self.id.debug_source())
+            .finish()
+    }
+}
+
+impl DebugSource for Arm<'_> {
+    fn fmt_source(&self, fmt: &mut Formatter<'_>) -> Result {
+        fmt.debug_struct("Arm")
+            .field("span", &self.span.debug_source())
+            .field("pattern", &self.pattern.debug_source())
+            .field("expr", &self.expr.debug_source())
+            .finish()
+    }
+}
+
+impl DebugSource for DefaultArm<'_> {
+    fn fmt_source(&self, fmt: &mut Formatter<'_>) -> Result {
+        fmt.debug_struct("DefaultArm")
+            .field("span", &self.span.debug_source())
+            .field("expr", &self.expr.debug_source())
+            .finish()
+    }
+}
+
+impl DebugSource for Assign<'_> {
+    fn fmt_source(&self, fmt: &mut Formatter<'_>) -> Result {
+        fmt.debug_struct("Assign")
+            .field("span", &self.span.debug_source())
+            .field("pattern", &self.pattern.debug_source())
+            .field("ty", &self.ty.debug_source())
+            .field("expr", &self.expr.debug_source())
+            .finish()
+    }
+}
+
+impl DebugSource for Statement<'_> {
+    fn fmt_source(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            Statement::Expr(e) => fmt.debug_tuple("Expr").field(&e.debug_source()).finish(),
+            Statement::Assign(a) => fmt.debug_tuple("Assign").field(&a.debug_source()).finish(),
+        }
+    }
+}
+
+impl DebugSource for App<'_> {
+    fn fmt_source(&self, fmt: &mut Formatter<'_>) -> Result {
+        fmt.debug_struct("App")
+            .field("span", &self.span.debug_source())
+            .field("inner", &self.inner.debug_source())
+            .field("arg_span", &self.arg_span.debug_source())
+            .field("args", &self.args.debug_source())
+            .finish()
+    }
+}
+
+impl DebugSource for Case<'_> {
+    fn fmt_source(&self, fmt: &mut Formatter<'_>) -> Result {
+        fmt.debug_struct("Case")
+            .field("span", &self.span.debug_source())
+            .field("subject", &self.subject.debug_source())
+            .field("arms", &self.arms.debug_source())
+            .field("default", &self.default.debug_source())
+            .finish()
+    }
+}
+
+impl DebugSource for Do<'_> {
+    fn fmt_source(&self, fmt: &mut Formatter<'_>) -> Result {
+        fmt.debug_struct("Do")
+            .field("span", &self.span.debug_source())
+            .field("statements", &self.statements.debug_source())
+            .field("ret", &self.ret.debug_source())
+            .finish()
+    }
+}
+
+impl DebugSource for Expr<'_> {
+    fn fmt_source(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            Expr::Int(span) => fmt.debug_tuple("Int").field(&span.debug_source()).finish(),
+            Expr::Float(span) => fmt
+                .debug_tuple("Float")
+                .field(&span.debug_source())
+                .finish(),
+            Expr::Tag(span, name) => fmt
+                .debug_tuple("Tag")
+                .field(&span.debug_source())
+                .field(&name.debug_source())
+                .finish(),
+            Expr::Id(span) => fmt.debug_tuple("Id").field(&span.debug_source()).finish(),
+            Expr::Expand(ellipsis) => fmt
+                .debug_tuple("Expand")
+                .field(&ellipsis.debug_source())
+                .finish(),
+            Expr::Tuple(span, xs) => fmt
+                .debug_tuple("Tuple")
+                .field(&span.debug_source())
+                .field(&xs.debug_source())
+                .finish(),
+            Expr::App(app) => app.fmt_source(fmt),
+            Expr::Case(case) => case.fmt_source(fmt),
+            Expr::Paren(span, inner) => fmt
+                .debug_tuple("Paren")
+                .field(&span.debug_source())
+                .field(&inner.debug_source())
+                .finish(),
+            Expr::Do(do_struct) => do_struct.fmt_source(fmt),
+            Expr::Fn(span, param, body) => fmt
+                .debug_tuple("Fn")
+                .field(&span.debug_source())
+                .field(&param.debug_source())
+                .field(&body.debug_source())
+                .finish(),
+        }
+    }
+}
+
+impl DebugSource for PatternApp<'_> {
+    fn fmt_source(&self, fmt: &mut Formatter<'_>) -> Result {
+        fmt.debug_struct("PatternApp")
+            .field("span", &self.span.debug_source())
+            .field("f", &self.f.debug_source())
+            .field("arg_span", &self.arg_span.debug_source())
+            .field("xs", &self.xs.debug_source())
+            .finish()
+    }
+}
+
+impl DebugSource for Pattern<'_> {
+    fn fmt_source(&self, fmt: &mut Formatter<'_>) -> Result {
+        match self {
+            Pattern::Id(span) => fmt.debug_tuple("Id").field(&span.debug_source()).finish(),
+            Pattern::Ignore(span) => fmt
+                .debug_tuple("Ignore")
+                .field(&span.debug_source())
+                .finish(),
+            Pattern::Int(span) => fmt.debug_tuple("Int").field(&span.debug_source()).finish(),
+            Pattern::Tag(span, name) => fmt
+                .debug_tuple("Tag")
+                .field(&span.debug_source())
+                .field(&name.debug_source())
+                .finish(),
+            Pattern::Collect(ellipsis) => fmt
+                .debug_tuple("Collect")
+                .field(&ellipsis.debug_source())
+                .finish(),
+            Pattern::Tuple(span, xs) => fmt
+                .debug_tuple("Tuple")
+                .field(&span.debug_source())
+                .field(&xs.debug_source())
+                .finish(),
+            Pattern::App(app) => app.fmt_source(fmt),
+            Pattern::Paren(span, inner) => fmt
+                .debug_tuple("Paren")
+                .field(&span.debug_source())
+                .field(&inner.debug_source())
+                .finish(),
+            Pattern::Or(span, xs) => fmt
+                .debug_tuple("Or")
+                .field(&span.debug_source())
+                .field(&xs.debug_source())
+                .finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::expr;
+
+    #[test]
+    fn test_debug_source_expr() {
+        let s = "f(x,\n  :y)";
+        let (_, e) = expr(s.into()).unwrap();
+        assert_eq!(
+            format!("{:?}", e.debug_source()),
+            "App { span: 1:1..2:6, inner: Id(1:1..1:2), arg_span: 1:2..2:6, \
+             args: [Id(1:3..1:4), Tag(2:3..2:5, 2:4..2:5)] }",
+        );
+    }
+
+    #[test]
+    fn test_debug_source_case() {
+        let s = "case x of 1 = x end";
+        let (_, e) = expr(s.into()).unwrap();
+        assert_eq!(
+            format!("{:?}", e.debug_source()),
+            "Case { span: 1:1..1:20, subject: Id(1:6..1:7), arms: [Arm { span: 1:8..1:16, \
+             pattern: Int(1:11..1:12), expr: Id(1:15..1:16) }], default: None }",
+        );
+    }
+}
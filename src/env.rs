@@ -109,7 +109,7 @@ mod test {
     #[should_panic]
     fn test_env() {
         let env = EnvVec::<String, usize>::new();
-        env["Hello"];
+        let _ = env["Hello"];
     }
 
     #[test]
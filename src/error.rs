@@ -0,0 +1,207 @@
+use crate::{
+    check::{TypeError, TypeErrorKind},
+    compile::{CompileError, CompileErrorKind},
+    eval::{RuntimeError, RuntimeErrorKind},
+    expr::Input,
+    parse::ParseError,
+};
+
+/// Any error from parsing, checking, compiling, or evaluating a source string
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Error<'a> {
+    Parse(ParseError<'a>),
+    Type(TypeError<'a>),
+    Compile(CompileError<'a>),
+    Runtime(RuntimeError<'a>),
+}
+
+impl<'a> From<ParseError<'a>> for Error<'a> {
+    fn from(err: ParseError<'a>) -> Self {
+        Self::Parse(err)
+    }
+}
+
+impl<'a> From<TypeError<'a>> for Error<'a> {
+    fn from(err: TypeError<'a>) -> Self {
+        Self::Type(err)
+    }
+}
+
+impl<'a> From<CompileError<'a>> for Error<'a> {
+    fn from(err: CompileError<'a>) -> Self {
+        Self::Compile(err)
+    }
+}
+
+impl<'a> From<RuntimeError<'a>> for Error<'a> {
+    fn from(err: RuntimeError<'a>) -> Self {
+        Self::Runtime(err)
+    }
+}
+
+impl<'a> Error<'a> {
+    fn category(&self) -> &'static str {
+        match self {
+            Self::Parse(_) => "parse error",
+            Self::Type(_) => "type error",
+            Self::Compile(_) => "compile error",
+            Self::Runtime(_) => "runtime error",
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Self::Parse(ParseError::InvalidUtf8(offset)) => {
+                format!("invalid UTF-8 at byte {offset}")
+            }
+            Self::Parse(ParseError::Unexpected(_)) => "unexpected input".to_string(),
+            Self::Parse(ParseError::Expected(what, _)) => format!("expected {what}"),
+            Self::Type(err) => match err.kind {
+                TypeErrorKind::UnknownType => format!("unknown type `{}`", err.span.as_inner()),
+                TypeErrorKind::Mismatch { expected, found } => {
+                    format!("expected {expected:?}, found {found:?}")
+                }
+            },
+            Self::Compile(err) => match err.kind {
+                CompileErrorKind::Unsupported => "not supported by the compiler yet".to_string(),
+            },
+            Self::Runtime(err) => match err.kind {
+                RuntimeErrorKind::Overflow => "literal out of range".to_string(),
+                RuntimeErrorKind::Unrepresentable => {
+                    "literal too large for a 64-bit signed integer".to_string()
+                }
+                RuntimeErrorKind::ArithmeticOverflow => "integer overflow".to_string(),
+                RuntimeErrorKind::DepthExceeded => "pattern nested too deeply".to_string(),
+                RuntimeErrorKind::CallDepthExceeded => "calls nested too deeply".to_string(),
+                RuntimeErrorKind::DivByZero => "division by zero".to_string(),
+                RuntimeErrorKind::TypeMismatch { expected } => format!("expected {expected:?}"),
+                RuntimeErrorKind::Unbound => "unbound variable".to_string(),
+                RuntimeErrorKind::NotCallable => "not a function".to_string(),
+                RuntimeErrorKind::NoMatch => "no arm matched".to_string(),
+                RuntimeErrorKind::Arity { expected, found } => {
+                    format!("expected {expected} arguments, found {found}")
+                }
+            },
+        }
+    }
+
+    fn span(&self) -> Option<Input<'a>> {
+        match self {
+            Self::Parse(ParseError::InvalidUtf8(_)) => None,
+            Self::Parse(ParseError::Unexpected(span) | ParseError::Expected(_, span)) => {
+                Some(*span)
+            }
+            Self::Type(err) => Some(err.span),
+            Self::Compile(err) => Some(err.span),
+            Self::Runtime(err) => err.span,
+        }
+    }
+
+    /// Describe the error for a user, quoting the line of `src` it occurred on
+    ///
+    /// ```text
+    /// type error at 1:13: expected Tag, found Int
+    /// { x : Tag = 1; x }
+    ///             ^
+    /// ```
+    pub(crate) fn render(&self, src: &str) -> String {
+        let (category, message) = (self.category(), self.message());
+        let Some(span) = self.span() else {
+            return format!("{category}: {message}");
+        };
+        let (line, col) = span.line_col();
+        let text = src.lines().nth(line - 1).unwrap_or("");
+        // Underline to the end of the span or the line, whichever comes first, and at least
+        // one char so that errors at the end of the source are visible
+        let len = span.as_inner().lines().next().unwrap_or("").chars().count();
+        let rest = text.chars().count().saturating_sub(col - 1);
+        let len = len.min(rest).max(1);
+        format!(
+            "{category} at {line}:{col}: {message}\n{text}\n{}{}",
+            " ".repeat(col - 1),
+            "^".repeat(len),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{check::check, compile::compile, eval::eval_str, parse::parse_expr};
+
+    #[test]
+    fn test_parse_error() {
+        let src = "f(x,\n  1 2)";
+        let err = Error::from(parse_expr(src).unwrap_err());
+        assert!(matches!(err, Error::Parse(_)));
+        assert_eq!(
+            err.render(src),
+            "parse error at 2:5: unexpected input\n  1 2)\n    ^^"
+        );
+
+        let src = "x ->";
+        let err = Error::from(parse_expr(src).unwrap_err());
+        assert_eq!(
+            err.render(src),
+            "parse error at 1:5: expected expression after '->'\nx ->\n    ^"
+        );
+
+        let err = Error::from(ParseError::InvalidUtf8(3));
+        assert_eq!(err.render(""), "parse error: invalid UTF-8 at byte 3");
+    }
+
+    #[test]
+    fn test_type_error() {
+        let src = "{ x : Tag = 1; x }";
+        let err = Error::from(check(&parse_expr(src).unwrap()).unwrap_err());
+        assert!(matches!(err, Error::Type(_)));
+        assert_eq!(
+            err.render(src),
+            "type error at 1:13: expected Tag, found Int\n{ x : Tag = 1; x }\n            ^"
+        );
+
+        let src = "{ x : Foo = 1; x }";
+        let err = Error::from(check(&parse_expr(src).unwrap()).unwrap_err());
+        assert_eq!(
+            err.render(src),
+            "type error at 1:7: unknown type `Foo`\n{ x : Foo = 1; x }\n      ^^^"
+        );
+    }
+
+    #[test]
+    fn test_compile_error() {
+        let src = "x -> x";
+        let err = compile(&parse_expr(src).unwrap()).unwrap_err();
+        assert!(matches!(err, Error::Compile(_)));
+        assert_eq!(
+            err.render(src),
+            "compile error at 1:1: not supported by the compiler yet\nx -> x\n^^^^^^"
+        );
+    }
+
+    #[test]
+    fn test_runtime_error() {
+        let src = "(1,\n  300u8)";
+        let err = eval_str(src).unwrap_err();
+        assert!(matches!(err, Error::Runtime(_)));
+        assert_eq!(
+            err.render(src),
+            "runtime error at 2:3: literal out of range\n  300u8)\n  ^^^^^"
+        );
+
+        let src = "add(9223372036854775807, 1)";
+        assert_eq!(
+            eval_str(src).unwrap_err().render(src),
+            format!(
+                "runtime error at 1:1: integer overflow\n{src}\n{}",
+                "^".repeat(src.len())
+            )
+        );
+
+        let err = Error::from(RuntimeError {
+            kind: RuntimeErrorKind::DepthExceeded,
+            span: None,
+        });
+        assert_eq!(err.render(""), "runtime error: pattern nested too deeply");
+    }
+}
@@ -1,15 +1,74 @@
 use crate::{
+    check::{check, Type},
     env::{Env as Environment, EnvVec},
-    expr::{Ellipsis, Expr, Input, Pattern, Statement},
+    error::Error,
+    expr::{Do, Ellipsis, Expr, Input, Pattern, Statement},
+    parse::parse_expr,
 };
 use std::{cell::RefCell, collections::HashSet, rc::Rc};
 use unwrap::unwrap;
 
-type Env<'a> = EnvVec<String, ValuePtr<'a>>;
+pub(crate) type Env<'a> = EnvVec<String, ValuePtr<'a>>;
 
-pub(crate) type Intrinsic<'a> = fn(&Value<'a>) -> Value<'a>;
+/// Any error from `eval_str`, under the name it had before parse, type, and runtime errors were
+/// unified
+pub(crate) type EvalError<'a> = Error<'a>;
 
-pub(crate) type Intrinsics<'a> = Vec<(&'static str, Intrinsic<'a>)>;
+/// A function implemented in Rust
+///
+/// It is only called with as many arguments as its arity. Errors without a span are located at
+/// the call.
+pub(crate) type BuiltinFn<'a> = fn(&[Value<'a>]) -> Result<Value<'a>, RuntimeError<'a>>;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum RuntimeErrorKind {
+    /// An integer literal does not fit in the range of its type, or a float literal is too large
+    Overflow,
+    /// An integer literal is in the range of its type, but not of the `i64` values are stored as,
+    /// such as `18446744073709551615u64`
+    Unrepresentable,
+    /// Integer arithmetic in a builtin overflowed, such as `add(9223372036854775807, 1)`
+    ArithmeticOverflow,
+    /// A pattern is nested more deeply than `MAX_MATCH_DEPTH`
+    DepthExceeded,
+    /// Closures are called more deeply than `MAX_CALL_DEPTH`, such as by unbounded recursion
+    CallDepthExceeded,
+    /// A builtin was called with the wrong number of arguments
+    Arity { expected: usize, found: usize },
+    /// An integer was divided by zero
+    DivByZero,
+    /// A builtin was given an argument of the wrong type
+    TypeMismatch { expected: Type },
+    /// A variable is not in scope
+    Unbound,
+    /// The callee of an application is not a function
+    NotCallable,
+    /// None of the arms of a case matched its subject
+    NoMatch,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct RuntimeError<'a> {
+    pub(crate) kind: RuntimeErrorKind,
+    pub(crate) span: Option<Input<'a>>,
+}
+
+impl<'a> RuntimeError<'a> {
+    pub(crate) fn new(kind: RuntimeErrorKind, span: Input<'a>) -> Self {
+        Self {
+            kind,
+            span: Some(span),
+        }
+    }
+
+    /// Locate the error at `span`, unless it is already located
+    pub(crate) fn or_at(self, span: Input<'a>) -> Self {
+        Self {
+            span: self.span.or(Some(span)),
+            ..self
+        }
+    }
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) struct Closure<'a> {
@@ -18,14 +77,22 @@ pub(crate) struct Closure<'a> {
     pub(crate) body: Expr<'a>,
 }
 
+#[derive(Clone)]
+pub(crate) struct Builtin<'a> {
+    pub(crate) name: String,
+    pub(crate) arity: usize,
+    pub(crate) f: BuiltinFn<'a>,
+}
+
 #[derive(Clone)]
 pub(crate) enum Value<'a> {
     Uninit,
     Int(i64),
+    Float(f64),
     Tag(&'a str),
     Tuple(Vec<ValuePtr<'a>>),
     Closure(Closure<'a>),
-    Intrinsic(Intrinsic<'a>),
+    Builtin(Builtin<'a>),
 }
 
 impl<'a> std::fmt::Debug for Value<'a> {
@@ -33,10 +100,14 @@ impl<'a> std::fmt::Debug for Value<'a> {
         match self {
             Value::Uninit => fmt.debug_tuple("Value::Uninit").finish(),
             Value::Int(x) => fmt.debug_tuple("Value::Int").field(x).finish(),
+            Value::Float(x) => fmt.debug_tuple("Value::Float").field(x).finish(),
             Value::Tag(tag) => fmt.debug_tuple("Value::Tag").field(tag).finish(),
             Value::Tuple(inner) => fmt.debug_tuple("Value::Tuple").field(inner).finish(),
             Value::Closure(closure) => fmt.debug_tuple("Value::Closure").field(closure).finish(),
-            Value::Intrinsic(_) => fmt.debug_tuple("Value::Intrinsic").finish(),
+            Value::Builtin(builtin) => fmt
+                .debug_tuple("Value::Builtin")
+                .field(&builtin.name)
+                .finish(),
         }
     }
 }
@@ -46,20 +117,31 @@ impl<'a> core::cmp::PartialEq for Value<'a> {
         match (self, other) {
             (Value::Uninit, Value::Uninit) => true,
             (Value::Int(x), Value::Int(y)) if x == y => true,
+            (Value::Float(x), Value::Float(y)) if x == y => true,
             (Value::Tag(x), Value::Tag(y)) if x == y => true,
             (Value::Tuple(x), Value::Tuple(y)) if x == y => true,
             (Value::Closure(x), Value::Closure(y)) if x == y => true,
-            (Value::Intrinsic(x), Value::Intrinsic(y)) if std::ptr::eq(x, y) => true,
+            (Value::Builtin(x), Value::Builtin(y))
+                if x.name == y.name && x.arity == y.arity && x.f as usize == y.f as usize =>
+            {
+                true
+            }
             _ => false,
         }
     }
 }
 
 impl<'a> Value<'a> {
-    pub(crate) fn get_i64(&self) -> i64 {
+    /// The integer, or an error for a builtin to return if the value is not one
+    pub(crate) fn as_int(&self) -> Result<i64, RuntimeError<'a>> {
         match self {
-            Value::Int(x) => *x,
-            _ => panic!("interpreter: expected i64: {:?}", self),
+            Value::Int(x) => Ok(*x),
+            _ => Err(RuntimeError {
+                kind: RuntimeErrorKind::TypeMismatch {
+                    expected: Type::Int,
+                },
+                span: None,
+            }),
         }
     }
 }
@@ -74,7 +156,197 @@ impl<'a> Value<'a> {
 
 pub(crate) type ValuePtr<'a> = Rc<RefCell<Value<'a>>>;
 
-fn expand_list<'a>(exprs: &Vec<Expr<'a>>, env: &mut Env<'a>) -> Vec<ValuePtr<'a>> {
+/// Maximum nesting of patterns during matching, so that hostile input errors instead of
+/// overflowing the stack
+pub(crate) const MAX_MATCH_DEPTH: usize = 256;
+
+/// Maximum nesting of calls to closures, so that runaway recursion errors instead of overflowing
+/// the stack
+///
+/// Each call takes several large frames of `eval` in debug builds, so this is kept low enough to fit
+/// in a 2 MiB thread stack.
+pub(crate) const MAX_CALL_DEPTH: usize = 64;
+
+/// Value of an integer literal such as `1_000` or `255u8`
+///
+/// The literal must fit in the range of its suffix, defaulting to `i64`. Values are stored as
+/// `i64`, so a `u64` literal above `i64::MAX` is in range but still an error.
+pub(crate) fn int_value(span: Input) -> Result<i64, RuntimeError> {
+    let (radix, text) = match span.as_inner().strip_prefix("0x") {
+        Some(hex) => (16, hex),
+        None => (10, span.as_inner()),
+    };
+    // Neither `i` nor `u` is a hex digit, so the suffix starts at the first of them
+    let (digits, suffix) = text.split_at(text.find(['i', 'u']).unwrap_or(text.len()));
+    let (min, max) = match suffix {
+        "i8" => (i8::MIN as i128, i8::MAX as i128),
+        "i16" => (i16::MIN as i128, i16::MAX as i128),
+        "i32" => (i32::MIN as i128, i32::MAX as i128),
+        "u8" => (0, u8::MAX as i128),
+        "u16" => (0, u16::MAX as i128),
+        "u32" => (0, u32::MAX as i128),
+        "u64" => (0, u64::MAX as i128),
+        "" | "i64" => (i64::MIN as i128, i64::MAX as i128),
+        _ => panic!("interpreter: unknown integer suffix: {span:?}"),
+    };
+    let overflow = || RuntimeError::new(RuntimeErrorKind::Overflow, span);
+    let value = i128::from_str_radix(&digits.replace('_', ""), radix).map_err(|_| overflow())?;
+    if !(min..=max).contains(&value) {
+        Err(overflow())
+    } else {
+        i64::try_from(value).map_err(|_| RuntimeError::new(RuntimeErrorKind::Unrepresentable, span))
+    }
+}
+
+/// Value of a float literal such as `1.5`, `2_000.0e-3`, or `0x1.8p3`, which must be finite
+pub(crate) fn float_value(span: Input) -> Result<f64, RuntimeError> {
+    let text = span.as_inner();
+    let value = match text.strip_prefix("0x") {
+        Some(hex) => hex_float_value(hex),
+        None => text
+            .replace('_', "")
+            .parse::<f64>()
+            .unwrap_or_else(|_| panic!("interpreter: malformed float literal: {span:?}")),
+    };
+    if value.is_finite() {
+        Ok(value)
+    } else {
+        Err(RuntimeError::new(RuntimeErrorKind::Overflow, span))
+    }
+}
+
+/// Value of a hex float without its `0x` prefix, such as `1.8p3`
+///
+/// The digits are read as an integer and rounded once, to the 53 bits of a normal float or to
+/// fewer for a subnormal, so that the result is the nearest float with ties to even.
+fn hex_float_value(hex: &str) -> f64 {
+    let (digits, exponent) = unwrap!(
+        hex.split_once(['p', 'P']),
+        "interpreter: hex float without exponent: {hex:?}"
+    );
+    let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+    // Exponents too large for an i32 overflow or underflow whatever the mantissa
+    let mut exponent = i64::from(
+        exponent
+            .parse::<i32>()
+            .unwrap_or(if exponent.starts_with('-') {
+                i32::MIN
+            } else {
+                i32::MAX
+            }),
+    );
+
+    // Keep the leading 61 to 64 bits, and whether any digit after them is nonzero
+    let mut mantissa = 0u64;
+    let mut sticky = false;
+    for (i, c) in int.chars().chain(frac.chars()).enumerate() {
+        let digit = u64::from(unwrap!(
+            c.to_digit(16),
+            "interpreter: not a hex digit: {c:?}"
+        ));
+        let is_frac = i >= int.len();
+        if mantissa >> 60 == 0 {
+            mantissa = mantissa << 4 | digit;
+            exponent -= 4 * i64::from(is_frac);
+        } else {
+            sticky |= digit != 0;
+            exponent += 4 * i64::from(!is_frac);
+        }
+    }
+    if mantissa == 0 {
+        return 0.0;
+    }
+
+    // The value is mantissa * 2^exponent, and its bits below 2^-1074 are lost as a subnormal
+    let len = i64::from(64 - mantissa.leading_zeros());
+    let precision = (exponent + len + 1074).min(53);
+    if precision < 0 {
+        // Less than half the smallest subnormal
+        return 0.0;
+    }
+    let shift = (len - precision).max(0);
+    let mantissa = u128::from(mantissa);
+    let mut rounded = mantissa >> shift;
+    if shift > 0 {
+        let rest = mantissa & ((1 << shift) - 1);
+        let half = 1 << (shift - 1);
+        if rest > half || rest == half && (sticky || rounded & 1 == 1) {
+            rounded += 1;
+        }
+    }
+
+    // Scale in steps, as 2^exponent alone may not be a finite nonzero float, and the rounded
+    // mantissa has few enough bits that every step is exact
+    let mut value = rounded as f64;
+    let mut exponent = exponent + shift;
+    while exponent.abs() > 1000 && value.is_finite() {
+        let step = 1000 * exponent.signum();
+        value *= 2f64.powi(step as i32);
+        exponent -= step;
+    }
+    value * 2f64.powi(exponent as i32)
+}
+
+impl<'a> Env<'a> {
+    /// Add a builtin function, callable from the language as `name`
+    pub(crate) fn with_builtin(mut self, name: &str, arity: usize, f: BuiltinFn<'a>) -> Self {
+        let builtin = Builtin {
+            name: name.to_string(),
+            arity,
+            f,
+        };
+        self.insert(name.to_string(), Value::Builtin(builtin).into_ptr());
+        self
+    }
+}
+
+/// The environment provided to `eval_str`
+pub(crate) fn default_env<'a>() -> Env<'a> {
+    fn add<'a>(args: &[Value<'a>]) -> Result<Value<'a>, RuntimeError<'a>> {
+        match args[0].as_int()?.checked_add(args[1].as_int()?) {
+            Some(x) => Ok(Value::Int(x)),
+            None => Err(RuntimeError {
+                kind: RuntimeErrorKind::ArithmeticOverflow,
+                span: None,
+            }),
+        }
+    }
+
+    fn div<'a>(args: &[Value<'a>]) -> Result<Value<'a>, RuntimeError<'a>> {
+        let (x, y) = (args[0].as_int()?, args[1].as_int()?);
+        let kind = match x.checked_div(y) {
+            Some(x) => return Ok(Value::Int(x)),
+            None if y == 0 => RuntimeErrorKind::DivByZero,
+            None => RuntimeErrorKind::ArithmeticOverflow,
+        };
+        Err(RuntimeError { kind, span: None })
+    }
+
+    Env::new()
+        .with_builtin("add", 2, add)
+        .with_builtin("div", 2, div)
+}
+
+/// Parse, check, and evaluate `src` in the default environment
+#[allow(dead_code)]
+pub(crate) fn eval_str(src: &str) -> Result<Value<'_>, EvalError<'_>> {
+    eval_str_in(src, &default_env())
+}
+
+/// Parse, check, and evaluate `src` in a copy of `env`
+#[allow(dead_code)]
+pub(crate) fn eval_str_in<'a>(src: &'a str, env: &Env<'a>) -> Result<Value<'a>, EvalError<'a>> {
+    let e = parse_expr(src)?;
+    check(&e)?;
+    Ok(e.eval_in(env)?)
+}
+
+/// Evaluate tuple items or arguments from left to right, stopping at the first error
+fn expand_list<'a>(
+    exprs: &Vec<Expr<'a>>,
+    env: &mut Env<'a>,
+    depth: usize,
+) -> Result<Vec<ValuePtr<'a>>, RuntimeError<'a>> {
     let mut xs = Vec::new();
     for elem in exprs {
         match elem {
@@ -86,29 +358,32 @@ fn expand_list<'a>(exprs: &Vec<Expr<'a>>, env: &mut Env<'a>) -> Vec<ValuePtr<'a>
                 }
             }
 
-            elem => xs.push(elem.eval(env).into_ptr()),
+            elem => xs.push(elem.eval(env, depth)?.into_ptr()),
         }
     }
-    xs
+    Ok(xs)
 }
 
 impl<'a> Expr<'a> {
-    pub(crate) fn eval_new(&'a self) -> Value<'a> {
-        let mut env = Env::new();
-        self.eval(&mut env)
+    #[allow(dead_code)]
+    pub(crate) fn eval_new(&'a self) -> Result<Value<'a>, RuntimeError<'a>> {
+        self.eval_in(&Env::new())
     }
 
-    pub(crate) fn eval_with_intrinsics(&self, fs: &Intrinsics<'a>) -> Value<'a> {
-        let mut env = Env::new();
-        for (k, v) in fs {
-            env.insert(k.to_string(), Value::Intrinsic(*v).into_ptr());
-        }
-        self.eval(&mut env)
+    /// Evaluate in a copy of `env`
+    ///
+    /// Integer literals out of range are reported before anything is evaluated.
+    pub(crate) fn eval_in(&self, env: &Env<'a>) -> Result<Value<'a>, RuntimeError<'a>> {
+        self.check_literals()?;
+        self.eval(&mut env.clone(), 0)
     }
 
-    fn eval(&self, env: &mut Env<'a>) -> Value<'a> {
-        match self {
-            Self::Int(span) => Value::Int(span.value_i64()),
+    /// Evaluate inside `depth` calls to closures
+    fn eval(&self, env: &mut Env<'a>, depth: usize) -> Result<Value<'a>, RuntimeError<'a>> {
+        Ok(match self {
+            Self::Int(span) => Value::Int(int_value(*span)?),
+
+            Self::Float(span) => Value::Float(float_value(*span)?),
 
             Self::Id(span) => env[span.as_inner()].borrow().clone(),
 
@@ -118,12 +393,19 @@ impl<'a> Expr<'a> {
                 "interpreter: expand expressions must be inside tuples: {self:?}"
             ),
 
-            Self::Tuple(_, inner) => Value::Tuple(expand_list(inner, env)),
+            Self::Tuple(_, inner) => Value::Tuple(expand_list(inner, env, depth)?),
 
-            Self::App(ref app) => match app.inner.eval(env) {
+            Self::App(ref app) => match app.inner.eval(env, depth)? {
                 Value::Closure(closure) => {
+                    if depth >= MAX_CALL_DEPTH {
+                        return Err(RuntimeError::new(
+                            RuntimeErrorKind::CallDepthExceeded,
+                            app.span,
+                        ));
+                    }
+
                     // Expand arguments to closure
-                    let args = expand_list(&app.args, env);
+                    let args = expand_list(&app.args, env, depth)?;
 
                     // Make sure args match closure
                     assert!(
@@ -139,18 +421,24 @@ impl<'a> Expr<'a> {
                     for (param, arg) in closure.params.iter().zip(args) {
                         closure_env.insert(param.as_inner().to_string(), arg);
                     }
-                    let value = closure.body.eval(&mut closure_env);
+                    let value = closure.body.eval(&mut closure_env, depth + 1);
                     closure_env.pop();
-                    value
+                    value?
                 }
 
-                Value::Intrinsic(f) => {
-                    let args = expand_list(&app.args, env);
-                    assert!(
-                        args.len() == 1,
-                        "interpreter: intrinsics take one parameter: {self:?}"
-                    );
-                    f(&Value::Tuple(args))
+                Value::Builtin(builtin) => {
+                    let args = expand_list(&app.args, env, depth)?;
+                    if args.len() != builtin.arity {
+                        return Err(RuntimeError::new(
+                            RuntimeErrorKind::Arity {
+                                expected: builtin.arity,
+                                found: args.len(),
+                            },
+                            app.span,
+                        ));
+                    }
+                    let args: Vec<_> = args.iter().map(|x| x.borrow().clone()).collect();
+                    (builtin.f)(&args).map_err(|err| err.or_at(app.span))?
                 }
 
                 x => panic!(
@@ -159,46 +447,32 @@ impl<'a> Expr<'a> {
             },
 
             Self::Case(case) => {
-                let subject_value = case.subject.eval(env);
+                let subject_value = case.subject.eval(env, depth)?;
                 for arm in &case.arms {
                     env.push();
-                    if arm.pattern.bind(&subject_value, env) {
-                        return arm.expr.eval(env);
-                    }
+                    let out = match arm.pattern.bind(&subject_value, env) {
+                        Ok(true) => Some(arm.expr.eval(env, depth)),
+                        Ok(false) => None,
+                        Err(err) => Some(Err(err)),
+                    };
                     env.pop();
+                    if let Some(out) = out {
+                        return out;
+                    }
+                }
+                match &case.default {
+                    Some(default) => default.expr.eval(env, depth)?,
+                    None => return Err(RuntimeError::new(RuntimeErrorKind::NoMatch, case.span)),
                 }
-                panic!(
-                    "interpreter: none of the case arms was found to match: {case:?}"
-                );
             }
 
-            Self::Paren(_, inner) => inner.eval(env),
+            Self::Paren(_, inner) => inner.eval(env, depth)?,
 
             Self::Do(inner) => {
                 env.push();
-                for statement in inner.statements.iter() {
-                    match statement {
-                        Statement::Expr(expr) => {
-                            expr.eval(env);
-                        }
-                        Statement::Assign(assign) => {
-                            let value = assign.expr.eval(env);
-                            if !assign.pattern.bind(&value, env) {
-                                panic!(
-                                    "interpreter: irrefutable pattern failed to bind: {:?}",
-                                    assign.pattern
-                                );
-                            }
-                        }
-                    }
-                }
-                let out = inner
-                    .ret
-                    .as_ref()
-                    .map(|e| e.eval(env))
-                    .unwrap_or(Value::UNIT);
+                let out = Self::eval_block(inner, env, depth);
                 env.pop();
-                out
+                out?
             }
 
             Self::Fn(_, param, inner) => {
@@ -220,6 +494,77 @@ impl<'a> Expr<'a> {
                 let body = (**inner).clone();
                 Value::Closure(Closure { env, params, body })
             }
+        })
+    }
+
+    /// Evaluate the statements and return value of a do-block, in a scope pushed by the caller
+    fn eval_block(
+        inner: &Do<'a>,
+        env: &mut Env<'a>,
+        depth: usize,
+    ) -> Result<Value<'a>, RuntimeError<'a>> {
+        for statement in inner.statements.iter() {
+            match statement {
+                Statement::Expr(expr) => {
+                    expr.eval(env, depth)?;
+                }
+                Statement::Assign(assign) => {
+                    let value = assign.expr.eval(env, depth)?;
+                    if !assign.pattern.bind(&value, env)? {
+                        panic!(
+                            "interpreter: irrefutable pattern failed to bind: {:?}",
+                            assign.pattern
+                        );
+                    }
+                }
+            }
+        }
+        inner
+            .ret
+            .as_ref()
+            .map(|e| e.eval(env, depth))
+            .unwrap_or(Ok(Value::UNIT))
+    }
+
+    /// Check that every integer literal fits its type, including literals in patterns and in
+    /// branches which are never evaluated
+    pub(crate) fn check_literals(&self) -> Result<(), RuntimeError<'a>> {
+        match self {
+            Self::Int(span) => int_value(*span).map(|_| ()),
+            Self::Float(span) => float_value(*span).map(|_| ()),
+            Self::Tag(..) | Self::Id(_) | Self::Expand(_) => Ok(()),
+            Self::Tuple(_, inner) => inner.iter().try_for_each(Self::check_literals),
+            Self::App(app) => {
+                app.inner.check_literals()?;
+                app.args.iter().try_for_each(Self::check_literals)
+            }
+            Self::Case(case) => {
+                case.subject.check_literals()?;
+                for arm in &case.arms {
+                    arm.pattern.check_literals()?;
+                    arm.expr.check_literals()?;
+                }
+                match &case.default {
+                    Some(default) => default.expr.check_literals(),
+                    None => Ok(()),
+                }
+            }
+            Self::Paren(_, inner) | Self::Fn(_, _, inner) => inner.check_literals(),
+            Self::Do(do_struct) => {
+                for statement in &do_struct.statements {
+                    match statement {
+                        Statement::Expr(e) => e.check_literals()?,
+                        Statement::Assign(assign) => {
+                            assign.pattern.check_literals()?;
+                            assign.expr.check_literals()?;
+                        }
+                    }
+                }
+                match &do_struct.ret {
+                    Some(e) => e.check_literals(),
+                    None => Ok(()),
+                }
+            }
         }
     }
 
@@ -242,6 +587,9 @@ impl<'a> Expr<'a> {
                     arm.expr.free(set);
                     arm.pattern.remove_bound(set);
                 }
+                if let Some(default) = &case.default {
+                    default.expr.free(set);
+                }
             }
             Self::Paren(_, inner) => inner.free(set),
             Self::Do(do_struct) => {
@@ -285,22 +633,54 @@ impl<'a> Pattern<'a> {
                 pattern_app.xs.iter().for_each(|p| p.remove_bound(set));
             }
             Self::Paren(_, inner) => inner.remove_bound(set),
+            Self::Or(_, inner) => inner.iter().for_each(|p| p.remove_bound(set)),
             _ => {}
         }
     }
 
-    fn bind(&self, value: &Value<'a>, env: &mut Env<'a>) -> bool {
+    fn check_literals(&self) -> Result<(), RuntimeError<'a>> {
         match self {
+            Self::Int(span) => int_value(*span).map(|_| ()),
+            Self::Id(_) | Self::Ignore(_) | Self::Tag(..) | Self::Collect(_) => Ok(()),
+            Self::Tuple(_, inner) | Self::Or(_, inner) => {
+                inner.iter().try_for_each(Self::check_literals)
+            }
+            Self::App(app) => {
+                app.f.check_literals()?;
+                app.xs.iter().try_for_each(Self::check_literals)
+            }
+            Self::Paren(_, inner) => inner.check_literals(),
+        }
+    }
+
+    fn bind(&self, value: &Value<'a>, env: &mut Env<'a>) -> Result<bool, RuntimeError<'a>> {
+        self.bind_at(value, env, 0)
+    }
+
+    /// Bind a pattern nested `depth` levels inside the pattern being matched
+    fn bind_at(
+        &self,
+        value: &Value<'a>,
+        env: &mut Env<'a>,
+        depth: usize,
+    ) -> Result<bool, RuntimeError<'a>> {
+        if depth > MAX_MATCH_DEPTH {
+            return Err(RuntimeError::new(
+                RuntimeErrorKind::DepthExceeded,
+                self.span(),
+            ));
+        }
+        Ok(match self {
             // id patterns bind unconditionally to the value
             Self::Id(id) => {
                 let key = id.as_inner();
-                match env.get(key).map(Clone::clone) {
-                    Some(inner) => match inner.replace(Value::Uninit) {
-                        Value::Uninit => {
-                            inner.replace(value.clone());
-                        }
-                        _ => env.insert(key.to_string(), value.clone().into_ptr()),
-                    },
+                match env.get(key) {
+                    // Fill in a capture made before the variable was assigned
+                    Some(inner) if matches!(*inner.borrow(), Value::Uninit) => {
+                        inner.replace(value.clone());
+                    }
+                    // Otherwise shadow it with a new cell, leaving the old value intact for outer
+                    // scopes and closures which captured it
                     _ => env.insert(key.to_string(), value.clone().into_ptr()),
                 }
                 true
@@ -310,13 +690,18 @@ impl<'a> Pattern<'a> {
             Self::Ignore(_) => true,
 
             // int patterns bind if the value is equal to the specified int
-            Self::Int(span) => matches!(value, Value::Int(y) if span.value_i64() == *y),
+            Self::Int(span) => {
+                let x = int_value(*span)?;
+                matches!(value, Value::Int(y) if x == *y)
+            }
 
             // tag pattern binds if the value is equal to the specified tag
             Self::Tag(_, span) => matches!(value, Value::Tag(tag) if span.as_inner() == *tag),
 
             // Bare collects are not allowed
-            Self::Collect(_) => panic!("interpreter: bare collect patterns are not allowed: {self:?}"),
+            Self::Collect(_) => {
+                panic!("interpreter: bare collect patterns are not allowed: {self:?}")
+            }
 
             // May include up to one collect pattern
             Self::Tuple(_, patterns) => {
@@ -324,7 +709,7 @@ impl<'a> Pattern<'a> {
                 let values = if let Value::Tuple(values) = value {
                     values
                 } else {
-                    return false
+                    return Ok(false);
                 };
 
                 let collect_count = patterns
@@ -338,11 +723,7 @@ impl<'a> Pattern<'a> {
 
                 if collect_count == 0 {
                     if patterns.len() == values.len() {
-                        patterns
-                            .iter()
-                            .zip(values.iter())
-                            .map(|(pat, ex)| pat.bind(&ex.borrow(), env))
-                            .all(|x| x)
+                        Self::bind_all(patterns, values, env, depth + 1)?
                     } else {
                         false
                     }
@@ -354,11 +735,12 @@ impl<'a> Pattern<'a> {
                         "interpreter: should be a collect pattern here: {:?}",
                         self
                     );
-                    let first = patterns[..collect_index]
-                        .iter()
-                        .zip(values[..collect_index].iter())
-                        .map(|(pat, ex)| pat.bind(&ex.borrow(), env))
-                        .all(|x| x);
+                    let first = Self::bind_all(
+                        &patterns[..collect_index],
+                        &values[..collect_index],
+                        env,
+                        depth + 1,
+                    )?;
                     let collect_values_count = (patterns.len() - 1) - values.len();
                     // collect values
                     let collected =
@@ -373,11 +755,12 @@ impl<'a> Pattern<'a> {
                     } else {
                         panic!("interpreter: there should be a collect pattern here: {self:?}");
                     }
-                    let second = patterns[collect_index + 1..]
-                        .iter()
-                        .zip(values[collect_index + collect_values_count..].iter())
-                        .map(|(pat, ex)| pat.bind(&ex.borrow(), env))
-                        .all(|x| x);
+                    let second = Self::bind_all(
+                        &patterns[collect_index + 1..],
+                        &values[collect_index + collect_values_count..],
+                        env,
+                        depth + 1,
+                    )?;
                     first && second
                 }
             }
@@ -385,29 +768,228 @@ impl<'a> Pattern<'a> {
             Self::App(_) => todo!(),
 
             // Obviously we just bind the inner pattern
-            Self::Paren(_, inner) => inner.bind(value, env),
+            Self::Paren(_, inner) => inner.bind_at(value, env, depth + 1)?,
+
+            // The first alternative which matches binds. Names bound by an alternative which then
+            // failed are bound again by the one which matches, as every alternative binds the same
+            // names.
+            Self::Or(_, alternatives) => {
+                for alternative in alternatives {
+                    if alternative.bind_at(value, env, depth + 1)? {
+                        return Ok(true);
+                    }
+                }
+                false
+            }
+        })
+    }
+
+    /// Bind each pattern to the corresponding value, stopping at the first that fails to match
+    fn bind_all(
+        patterns: &[Pattern<'a>],
+        values: &[ValuePtr<'a>],
+        env: &mut Env<'a>,
+        depth: usize,
+    ) -> Result<bool, RuntimeError<'a>> {
+        for (pat, value) in patterns.iter().zip(values) {
+            if !pat.bind_at(&value.borrow(), env, depth)? {
+                return Ok(false);
+            }
         }
+        Ok(true)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::expr;
+    use crate::{
+        parse::{expr, ParseError},
+        span::span,
+    };
 
     macro_rules! evals_to {
         ($s: expr, $v: expr) => {
             if let Ok((_, x)) = expr($s.into()) {
-                assert_eq!(x.eval_new(), $v);
+                assert_eq!(x.eval_new(), Ok($v));
             } else {
                 assert!(false);
             }
         };
     }
 
+    macro_rules! fails_with {
+        ($s: expr, $kind: expr, $span: expr) => {
+            if let Ok((_, x)) = expr($s.into()) {
+                let err = x.eval_new().unwrap_err();
+                assert_eq!(err.kind, $kind);
+                assert_eq!(err.span.map(|span| span.as_inner()), Some($span));
+            } else {
+                assert!(false);
+            }
+        };
+    }
+
+    #[test]
+    fn test_eval_str() {
+        assert_eq!(eval_str("add(2, 3)"), Ok(Value::Int(5)));
+        assert_eq!(eval_str("{ x = add(1, 1); add(x, x) }"), Ok(Value::Int(4)));
+        assert_eq!(
+            eval_str("add(1, "),
+            Err(Error::Parse(ParseError::Unexpected(Input::new(
+                "add(1, ", 7, 7
+            )))),
+        );
+        assert_eq!(
+            eval_str("300u8"),
+            Err(Error::Runtime(RuntimeError::new(
+                RuntimeErrorKind::Overflow,
+                Input::new("300u8", 0, 5)
+            ))),
+        );
+    }
+
+    #[test]
+    fn test_eval_str_in() {
+        let mut env = default_env();
+        env.insert("x".to_string(), Value::Int(2).into_ptr());
+        assert_eq!(eval_str_in("add(x, 3)", &env), Ok(Value::Int(5)));
+        // The caller's environment is left untouched
+        assert_eq!(eval_str_in("{ x = 3; x }", &env), Ok(Value::Int(3)));
+        assert_eq!(eval_str_in("x", &env), Ok(Value::Int(2)));
+    }
+
+    #[test]
+    fn test_shadowing() {
+        evals_to!(
+            "{ x = 1; y = { x = 2; x }; (x, y) }",
+            Value::Tuple(vec![Value::Int(1).into_ptr(), Value::Int(2).into_ptr()])
+        );
+        evals_to!(
+            "{ x = 1; f = y -> x; x = 2; (f(0), x) }",
+            Value::Tuple(vec![Value::Int(1).into_ptr(), Value::Int(2).into_ptr()])
+        );
+    }
+
+    #[test]
+    fn test_with_builtin() {
+        fn twice<'a>(args: &[Value<'a>]) -> Result<Value<'a>, RuntimeError<'a>> {
+            Ok(Value::Int(args[0].as_int()? * 2))
+        }
+
+        let env = default_env().with_builtin("twice", 1, twice);
+        assert_eq!(eval_str_in("twice(21)", &env), Ok(Value::Int(42)));
+        assert_eq!(
+            eval_str_in("{ f = x -> twice(add(x, 1)); f(2) }", &env),
+            Ok(Value::Int(6))
+        );
+
+        let s = "twice(1, 2)";
+        assert_eq!(
+            eval_str_in(s, &env),
+            Err(Error::Runtime(RuntimeError::new(
+                RuntimeErrorKind::Arity {
+                    expected: 1,
+                    found: 2
+                },
+                Input::from(s)
+            ))),
+        );
+    }
+
+    #[test]
+    fn test_builtin_error_span() {
+        let s = "(1, add(9223372036854775807, 1))";
+        assert_eq!(
+            eval_str(s),
+            Err(Error::Runtime(RuntimeError::new(
+                RuntimeErrorKind::ArithmeticOverflow,
+                Input::new(s, 4, 31)
+            ))),
+        );
+    }
+
+    #[test]
+    fn test_builtin_type_mismatch() {
+        let s = "add(:a, 1)";
+        assert_eq!(
+            eval_str(s),
+            Err(Error::Runtime(RuntimeError::new(
+                RuntimeErrorKind::TypeMismatch {
+                    expected: Type::Int
+                },
+                Input::from(s)
+            ))),
+        );
+        assert!(matches!(
+            eval_str("add(1, (1, 2))"),
+            Err(Error::Runtime(RuntimeError {
+                kind: RuntimeErrorKind::TypeMismatch { .. },
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_div() {
+        assert_eq!(eval_str("div(7, 2)"), Ok(Value::Int(3)));
+        let s = "div((), 1)";
+        assert_eq!(
+            eval_str(s),
+            Err(Error::Runtime(RuntimeError::new(
+                RuntimeErrorKind::TypeMismatch {
+                    expected: Type::Int
+                },
+                Input::from(s)
+            ))),
+        );
+        assert!(eval_str("div(1, :a)").is_err());
+        let s = "div(1, 0)";
+        assert_eq!(
+            eval_str(s),
+            Err(Error::Runtime(RuntimeError::new(
+                RuntimeErrorKind::DivByZero,
+                Input::from(s)
+            ))),
+        );
+    }
+
+    #[test]
+    fn test_short_circuit() {
+        thread_local! {
+            static TRACE: RefCell<Vec<i64>> = const { RefCell::new(Vec::new()) };
+        }
+
+        /// Record the argument, then return it
+        fn trace<'a>(args: &[Value<'a>]) -> Result<Value<'a>, RuntimeError<'a>> {
+            let x = args[0].as_int()?;
+            TRACE.with(|trace| trace.borrow_mut().push(x));
+            Ok(args[0].clone())
+        }
+
+        let env = default_env().with_builtin("trace", 1, trace);
+        let s = "(trace(1), div(trace(2), 0), trace(3))";
+        assert_eq!(
+            eval_str_in(s, &env),
+            Err(Error::Runtime(RuntimeError::new(
+                RuntimeErrorKind::DivByZero,
+                Input::new(s, 11, 27)
+            ))),
+        );
+        assert_eq!(TRACE.with(|trace| trace.take()), vec![1, 2]);
+
+        // Arguments are evaluated the same way
+        let s = "add(trace(1), add(trace(2), div(trace(3), 0)), trace(4))";
+        assert!(eval_str_in(s, &env).is_err());
+        assert_eq!(TRACE.with(|trace| trace.take()), vec![1, 2, 3]);
+    }
+
     #[test]
     fn test_eval_int() {
         evals_to!("1234", Value::Int(1234));
+        evals_to!("0xFF", Value::Int(255));
+        evals_to!("0xdead_BEEF", Value::Int(0xdead_beef));
+        evals_to!("0x7fffffffffffffff", Value::Int(i64::MAX));
     }
 
     #[test]
@@ -475,4 +1057,212 @@ mod test {
             Value::Int(8)
         );
     }
+
+    #[test]
+    fn test_case_default() {
+        evals_to!(
+            "case 3 of 1 = :one of else = :other end",
+            Value::Tag("other")
+        );
+        evals_to!("case 1 of 1 = :one of else = :other end", Value::Tag("one"));
+    }
+
+    #[test]
+    fn test_case_or() {
+        for (subject, value) in [(":a", 1), (":b", 1), (":c", 2)] {
+            let s = format!("case {subject} of :a | :b = 1 of else = 2 end");
+            assert_eq!(eval_str(&s), Ok(Value::Int(value)), "{s}");
+        }
+        // x is bound by the second alternative after the first fails on 1
+        evals_to!("case (2, 5) of (1, x) | (2, x) = x end", Value::Int(5));
+        evals_to!("{ (x, _) | (_, x) = (1, 2); x }", Value::Int(1));
+    }
+
+    #[test]
+    fn test_case_no_match() {
+        fails_with!(
+            "(1, case 2 of 1 = :one end)",
+            RuntimeErrorKind::NoMatch,
+            "case 2 of 1 = :one end"
+        );
+    }
+
+    #[test]
+    fn test_int_suffix() {
+        evals_to!("255u8", Value::Int(255));
+        evals_to!("1_000i16", Value::Int(1000));
+        evals_to!("9223372036854775807", Value::Int(i64::MAX));
+        fails_with!("256u8", RuntimeErrorKind::Overflow, "256u8");
+        evals_to!("9223372036854775807u64", Value::Int(i64::MAX));
+        fails_with!(
+            "9223372036854775808u64",
+            RuntimeErrorKind::Unrepresentable,
+            "9223372036854775808u64"
+        );
+        fails_with!(
+            "0xffff_ffff_ffff_ffffu64",
+            RuntimeErrorKind::Unrepresentable,
+            "0xffff_ffff_ffff_ffffu64"
+        );
+        fails_with!(
+            "18446744073709551616u64",
+            RuntimeErrorKind::Overflow,
+            "18446744073709551616u64"
+        );
+        evals_to!("0xffu8", Value::Int(255));
+        fails_with!("0x1_00u8", RuntimeErrorKind::Overflow, "0x1_00u8");
+        fails_with!("(1, 70_000u16)", RuntimeErrorKind::Overflow, "70_000u16");
+        fails_with!(
+            "9223372036854775808",
+            RuntimeErrorKind::Overflow,
+            "9223372036854775808"
+        );
+    }
+
+    #[test]
+    fn test_eval_float() {
+        evals_to!("1.5", Value::Float(1.5));
+        evals_to!("1_000.25e-2", Value::Float(10.0025));
+        evals_to!("2.0E3", Value::Float(2000.0));
+        fails_with!("(1, 1.0e999)", RuntimeErrorKind::Overflow, "1.0e999");
+    }
+
+    #[test]
+    fn test_eval_hex_float() {
+        evals_to!("0x1.8p3", Value::Float(12.0));
+        evals_to!("0x1p-2", Value::Float(0.25));
+        evals_to!("0xA.Fp+1", Value::Float(21.875));
+        evals_to!("0x1.P0", Value::Float(1.0));
+        evals_to!("0x1p-99999999999", Value::Float(0.0));
+        evals_to!("0x0p99999999999", Value::Float(0.0));
+        // Subnormals, rounding to the nearest multiple of the smallest with ties to even
+        evals_to!("0x1000p-1086", Value::Float(5e-324));
+        evals_to!("0x1p-1074", Value::Float(5e-324));
+        evals_to!("0x1.8p-1075", Value::Float(5e-324));
+        evals_to!("0x1p-1075", Value::Float(0.0));
+        evals_to!("0x3p-1075", Value::Float(1e-323));
+        evals_to!("0x0.0000000000001p-1022", Value::Float(5e-324));
+        // Mantissas wider than a float are rounded once, not after every digit
+        evals_to!("0x1.00000000000008p0", Value::Float(1.0));
+        evals_to!("0x1.000000000000081p0", Value::Float(1.0 + f64::EPSILON));
+        evals_to!(
+            "0x1.000000000000080000000000000001p0",
+            Value::Float(1.0 + f64::EPSILON)
+        );
+        evals_to!("0x10000000000000000000p-76", Value::Float(1.0));
+        evals_to!("0x1.fffffffffffffp1023", Value::Float(f64::MAX));
+        fails_with!(
+            "0x1.fffffffffffff8p1023",
+            RuntimeErrorKind::Overflow,
+            "0x1.fffffffffffff8p1023"
+        );
+        fails_with!("0x1p1024", RuntimeErrorKind::Overflow, "0x1p1024");
+        fails_with!(
+            "0x1p99999999999",
+            RuntimeErrorKind::Overflow,
+            "0x1p99999999999"
+        );
+    }
+
+    #[test]
+    fn test_int_suffix_pattern() {
+        evals_to!("case 255 of 255u8 = :yes end", Value::Tag("yes"));
+        fails_with!(
+            "case 1 of 256u8 = :yes end",
+            RuntimeErrorKind::Overflow,
+            "256u8"
+        );
+    }
+
+    #[test]
+    fn test_int_suffix_unevaluated() {
+        // Literals are checked even where they are never evaluated
+        fails_with!(
+            "case 1 of 1 = :a of 256u8 = :b end",
+            RuntimeErrorKind::Overflow,
+            "256u8"
+        );
+        fails_with!("{ f = x -> 300u8; 1 }", RuntimeErrorKind::Overflow, "300u8");
+        fails_with!(
+            "case 1 of x = x of else = 1_000i8 end",
+            RuntimeErrorKind::Overflow,
+            "1_000i8"
+        );
+    }
+
+    #[test]
+    fn test_scope_popped() {
+        // Bindings of an arm or block do not outlive it, whether or not it fails
+        let mut env = default_env();
+        let e = parse_expr("{ y = 1; case y of z = z end }").unwrap();
+        assert_eq!(e.eval(&mut env, 0), Ok(Value::Int(1)));
+        assert!(!env.contains("y") && !env.contains("z"));
+
+        let e = parse_expr("{ y = 1; div(y, 0) }").unwrap();
+        assert!(e.eval(&mut env, 0).is_err());
+        assert!(!env.contains("y"));
+
+        let e = parse_expr("case 1 of z = div(z, 0) end").unwrap();
+        assert!(e.eval(&mut env, 0).is_err());
+        assert!(!env.contains("z"));
+    }
+
+    #[test]
+    fn test_match_depth() {
+        // (((x,),),) nested `depth` times, matched against a value of the same shape
+        fn nested<'a>(s: &'a str, depth: usize) -> (Pattern<'a>, Value<'a>) {
+            let span = span!(s);
+            let mut pattern = Pattern::Id(span);
+            let mut value = Value::Int(1);
+            for _ in 0..depth {
+                pattern = Pattern::Tuple(span, vec![pattern]);
+                value = Value::Tuple(vec![value.into_ptr()]);
+            }
+            (pattern, value)
+        }
+
+        let s = "x";
+        let (pattern, value) = nested(s, MAX_MATCH_DEPTH);
+        assert_eq!(pattern.bind(&value, &mut Env::new()), Ok(true));
+
+        let (pattern, value) = nested(s, MAX_MATCH_DEPTH + 1);
+        assert_eq!(
+            pattern.bind(&value, &mut Env::new()),
+            Err(RuntimeError::new(RuntimeErrorKind::DepthExceeded, span!(s))),
+        );
+    }
+
+    #[test]
+    fn test_call_depth() {
+        // f(0) calls f until n reaches `calls`, making `calls + 1` nested calls
+        let recurse = |calls| {
+            format!("{{ f = n -> case n of {calls} = n of else = f(add(n, 1)) end; f(0) }}")
+        };
+
+        let s = recurse(MAX_CALL_DEPTH - 1);
+        assert_eq!(eval_str(&s), Ok(Value::Int(MAX_CALL_DEPTH as i64 - 1)));
+
+        let s = recurse(MAX_CALL_DEPTH);
+        let err = eval_str(&s).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                Error::Runtime(RuntimeError {
+                    kind: RuntimeErrorKind::CallDepthExceeded,
+                    ..
+                })
+            ),
+            "{err:?}"
+        );
+
+        // Unbounded recursion errors instead of overflowing the stack
+        let s = "{ f = x -> f(x); f(1) }";
+        assert_eq!(
+            eval_str(s),
+            Err(Error::Runtime(RuntimeError::new(
+                RuntimeErrorKind::CallDepthExceeded,
+                Input::new(s, 11, 15)
+            )))
+        );
+    }
 }
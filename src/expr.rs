@@ -1,58 +1,70 @@
 use crate::span::Span;
+use std::collections::BTreeSet;
 
-pub(crate) type Input<'a> = Span<&'a str>;
+pub type Input<'a> = Span<&'a str>;
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct Arm<'a> {
-    pub(crate) span: Input<'a>,
-    pub(crate) pattern: Pattern<'a>,
-    pub(crate) expr: Expr<'a>,
+pub struct Arm<'a> {
+    pub span: Input<'a>,
+    pub pattern: Pattern<'a>,
+    pub expr: Expr<'a>,
 }
 
+/// The catch-all `of else = expr` arm, which must come last in a case
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct Ellipsis<'a> {
-    pub(crate) span: Input<'a>,
-    pub(crate) id: Option<Input<'a>>,
+pub struct DefaultArm<'a> {
+    pub span: Input<'a>,
+    pub expr: Expr<'a>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct Assign<'a> {
-    pub(crate) span: Input<'a>,
-    pub(crate) pattern: Pattern<'a>,
-    pub(crate) expr: Expr<'a>,
+pub struct Ellipsis<'a> {
+    pub span: Input<'a>,
+    pub id: Option<Input<'a>>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) enum Statement<'a> {
+pub struct Assign<'a> {
+    pub span: Input<'a>,
+    pub pattern: Pattern<'a>,
+    /// Name of the type in `pattern : Type = expr`, which is only used by the type checker
+    pub ty: Option<Input<'a>>,
+    pub expr: Expr<'a>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Statement<'a> {
     Expr(Expr<'a>),
     Assign(Assign<'a>),
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct App<'a> {
-    pub(crate) span: Input<'a>,
-    pub(crate) inner: Box<Expr<'a>>,
-    pub(crate) arg_span: Input<'a>,
-    pub(crate) args: Vec<Expr<'a>>,
+pub struct App<'a> {
+    pub span: Input<'a>,
+    pub inner: Box<Expr<'a>>,
+    pub arg_span: Input<'a>,
+    pub args: Vec<Expr<'a>>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct Case<'a> {
-    pub(crate) span: Input<'a>,
-    pub(crate) subject: Box<Expr<'a>>,
-    pub(crate) arms: Vec<Arm<'a>>,
+pub struct Case<'a> {
+    pub span: Input<'a>,
+    pub subject: Box<Expr<'a>>,
+    pub arms: Vec<Arm<'a>>,
+    pub default: Option<Box<DefaultArm<'a>>>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct Do<'a> {
-    pub(crate) span: Input<'a>,
-    pub(crate) statements: Vec<Statement<'a>>,
-    pub(crate) ret: Option<Box<Expr<'a>>>,
+pub struct Do<'a> {
+    pub span: Input<'a>,
+    pub statements: Vec<Statement<'a>>,
+    pub ret: Option<Box<Expr<'a>>>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) enum Expr<'a> {
+pub enum Expr<'a> {
     Int(Input<'a>),
+    Float(Input<'a>),
     Tag(Input<'a>, Input<'a>),
     Id(Input<'a>),
     Expand(Ellipsis<'a>),
@@ -65,15 +77,15 @@ pub(crate) enum Expr<'a> {
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) struct PatternApp<'a> {
-    pub(crate) span: Input<'a>,
-    pub(crate) f: Box<Pattern<'a>>,
-    pub(crate) arg_span: Input<'a>,
-    pub(crate) xs: Vec<Pattern<'a>>,
+pub struct PatternApp<'a> {
+    pub span: Input<'a>,
+    pub f: Box<Pattern<'a>>,
+    pub arg_span: Input<'a>,
+    pub xs: Vec<Pattern<'a>>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
-pub(crate) enum Pattern<'a> {
+pub enum Pattern<'a> {
     Id(Input<'a>),
     Ignore(Input<'a>),
     Int(Input<'a>),
@@ -82,4 +94,292 @@ pub(crate) enum Pattern<'a> {
     Tuple(Input<'a>, Vec<Pattern<'a>>),
     App(PatternApp<'a>),
     Paren(Input<'a>, Box<Pattern<'a>>),
+    /// Two or more alternatives, each binding the same names
+    Or(Input<'a>, Vec<Pattern<'a>>),
+}
+
+impl<'a> Expr<'a> {
+    /// The precedence of atoms, which is the highest
+    pub(crate) const ATOM_PRECEDENCE: u8 = 5;
+
+    /// How tightly the expression binds, from loosest to tightest:
+    ///
+    /// 0. functions `x -> x`, whose body extends as far as possible
+    /// 1. tuples `x, y`
+    /// 2. expansions `..x`, which may only appear as tuple items or arguments
+    /// 3. `case` and do-blocks, which are self-delimiting but cannot be called directly
+    /// 4. applications `f(x)`
+    /// 5. atoms, see `is_atom`
+    ///
+    /// An expression needs parentheses wherever its context requires a higher precedence.
+    #[allow(dead_code)]
+    pub(crate) fn precedence(&self) -> u8 {
+        match self {
+            Expr::Fn(..) => 0,
+            Expr::Tuple(_, xs) if !xs.is_empty() => 1,
+            Expr::Expand(_) => 2,
+            Expr::Case(_) | Expr::Do(_) => 3,
+            Expr::App(_) => 4,
+            Expr::Int(_)
+            | Expr::Float(_)
+            | Expr::Tag(..)
+            | Expr::Id(_)
+            | Expr::Paren(..)
+            | Expr::Tuple(..) => Self::ATOM_PRECEDENCE,
+        }
+    }
+
+    /// Whether the expression never needs parentheses: literals, ids, `()`, and parenthesized
+    /// expressions
+    #[allow(dead_code)]
+    pub(crate) fn is_atom(&self) -> bool {
+        self.precedence() == Self::ATOM_PRECEDENCE
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn span(&self) -> Input<'a> {
+        match self {
+            Expr::Int(span)
+            | Expr::Float(span)
+            | Expr::Tag(span, _)
+            | Expr::Id(span)
+            | Expr::Tuple(span, _)
+            | Expr::Paren(span, _)
+            | Expr::Fn(span, ..) => *span,
+            Expr::Expand(ellipsis) => ellipsis.span,
+            Expr::App(app) => app.span,
+            Expr::Case(case) => case.span,
+            Expr::Do(do_struct) => do_struct.span,
+        }
+    }
+
+    /// The expression with any enclosing parentheses removed, as they do not affect evaluation
+    #[allow(dead_code)]
+    pub(crate) fn strip_parens(&self) -> &Expr<'a> {
+        match self {
+            Expr::Paren(_, inner) => inner.strip_parens(),
+            e => e,
+        }
+    }
+}
+
+/// Whether `e` is parenthesized only for grouping, as in `(x)`, rather than delimiting a tuple,
+/// as in `(x,)` or `(x, y)`
+#[allow(dead_code)]
+pub(crate) fn is_grouping_paren(e: &Expr) -> bool {
+    matches!(e, Expr::Paren(_, inner) if !matches!(**inner, Expr::Tuple(..)))
+}
+
+impl<'a> Pattern<'a> {
+    /// The precedence of atoms, which is the highest
+    pub(crate) const ATOM_PRECEDENCE: u8 = 4;
+
+    /// How tightly the pattern binds, from loosest to tightest:
+    ///
+    /// 0. alternatives `x | y`
+    /// 1. tuples `x, y`
+    /// 2. collections `..xs`, which may only appear as tuple items or arguments
+    /// 3. applications `f(x)`
+    /// 4. atoms: literals, ids, `_x`, `()`, and parenthesized patterns
+    ///
+    /// As with `Expr::precedence`, a pattern needs parentheses wherever its context requires a
+    /// higher precedence.
+    #[allow(dead_code)]
+    pub(crate) fn precedence(&self) -> u8 {
+        match self {
+            Pattern::Or(..) => 0,
+            Pattern::Tuple(_, xs) if !xs.is_empty() => 1,
+            Pattern::Collect(_) => 2,
+            Pattern::App(_) => 3,
+            Pattern::Id(_)
+            | Pattern::Ignore(_)
+            | Pattern::Int(_)
+            | Pattern::Tag(..)
+            | Pattern::Paren(..)
+            | Pattern::Tuple(..) => Self::ATOM_PRECEDENCE,
+        }
+    }
+
+    pub(crate) fn span(&self) -> Input<'a> {
+        match self {
+            Pattern::Id(span)
+            | Pattern::Ignore(span)
+            | Pattern::Int(span)
+            | Pattern::Tag(span, _)
+            | Pattern::Tuple(span, _)
+            | Pattern::Paren(span, _)
+            | Pattern::Or(span, _) => *span,
+            Pattern::Collect(ellipsis) => ellipsis.span,
+            Pattern::App(app) => app.span,
+        }
+    }
+
+    /// The names the pattern binds
+    pub(crate) fn names(&self) -> BTreeSet<&'a str> {
+        fn add<'a>(p: &Pattern<'a>, names: &mut BTreeSet<&'a str>) {
+            match p {
+                Pattern::Id(id) => {
+                    names.insert(id.as_inner());
+                }
+                Pattern::Collect(ellipsis) => names.extend(ellipsis.id.map(|id| id.as_inner())),
+                Pattern::Tuple(_, xs) => xs.iter().for_each(|x| add(x, names)),
+                Pattern::App(app) => {
+                    add(&app.f, names);
+                    app.xs.iter().for_each(|x| add(x, names));
+                }
+                Pattern::Paren(_, inner) => add(inner, names),
+                // Every alternative binds the same names
+                Pattern::Or(_, xs) => add(&xs[0], names),
+                Pattern::Ignore(_) | Pattern::Int(_) | Pattern::Tag(..) => {}
+            }
+        }
+        let mut names = BTreeSet::new();
+        add(self, &mut names);
+        names
+    }
+}
+
+/// The structure of an expression without spans or literal values, for asserting the shape of a
+/// parse in tests: `f(x, y)` is `App(Id, [Id, Id])`
+#[cfg(test)]
+pub(crate) fn shape(e: &Expr) -> String {
+    fn list<T>(xs: &[T], f: impl Fn(&T) -> String) -> String {
+        let xs: Vec<_> = xs.iter().map(f).collect();
+        format!("[{}]", xs.join(", "))
+    }
+
+    fn pattern(p: &Pattern) -> String {
+        match p {
+            Pattern::Id(_) => "Id".to_string(),
+            Pattern::Ignore(_) => "Ignore".to_string(),
+            Pattern::Int(_) => "Int".to_string(),
+            Pattern::Tag(..) => "Tag".to_string(),
+            Pattern::Collect(_) => "Collect".to_string(),
+            Pattern::Tuple(_, xs) => format!("Tuple{}", list(xs, pattern)),
+            Pattern::App(app) => format!("App({}, {})", pattern(&app.f), list(&app.xs, pattern)),
+            Pattern::Paren(_, inner) => format!("Paren({})", pattern(inner)),
+            Pattern::Or(_, xs) => format!("Or{}", list(xs, pattern)),
+        }
+    }
+
+    fn statement(statement: &Statement) -> String {
+        match statement {
+            Statement::Expr(e) => format!("Expr({})", shape(e)),
+            Statement::Assign(assign) => {
+                format!(
+                    "Assign({}, {})",
+                    pattern(&assign.pattern),
+                    shape(&assign.expr)
+                )
+            }
+        }
+    }
+
+    match e {
+        Expr::Int(_) => "Int".to_string(),
+        Expr::Float(_) => "Float".to_string(),
+        Expr::Tag(..) => "Tag".to_string(),
+        Expr::Id(_) => "Id".to_string(),
+        Expr::Expand(_) => "Expand".to_string(),
+        Expr::Tuple(_, xs) => format!("Tuple{}", list(xs, shape)),
+        Expr::App(app) => format!("App({}, {})", shape(&app.inner), list(&app.args, shape)),
+        Expr::Case(case) => {
+            let arms = list(&case.arms, |arm| {
+                format!("Arm({}, {})", pattern(&arm.pattern), shape(&arm.expr))
+            });
+            match &case.default {
+                Some(default) => format!(
+                    "Case({}, {arms}, Else({}))",
+                    shape(&case.subject),
+                    shape(&default.expr)
+                ),
+                None => format!("Case({}, {arms})", shape(&case.subject)),
+            }
+        }
+        Expr::Paren(_, inner) => format!("Paren({})", shape(inner)),
+        Expr::Do(do_struct) => {
+            let statements = list(&do_struct.statements, statement);
+            match &do_struct.ret {
+                Some(ret) => format!("Do({statements}, {})", shape(ret)),
+                None => format!("Do({statements})"),
+            }
+        }
+        Expr::Fn(_, _, body) => format!("Fn({})", shape(body)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parse::{parse_expr, parse_pattern};
+
+    #[test]
+    fn test_shape() {
+        let shape = |s| shape(&parse_expr(s).unwrap());
+        assert_eq!(shape("f(x, y)"), "App(Id, [Id, Id])");
+        assert_eq!(shape("f(x)(:y, ..zs)"), "App(App(Id, [Id]), [Tag, Expand])");
+        assert_eq!(
+            shape("1, (2,), ()"),
+            "Tuple[Int, Paren(Tuple[Int]), Tuple[]]"
+        );
+        assert_eq!(shape("x y -> f(x)"), "Fn(Fn(App(Id, [Id])))");
+        assert_eq!(
+            shape("case x of (a, ..) = a of :some(b) = b of else = 0 end"),
+            "Case(Id, [Arm(Paren(Tuple[Id, Collect]), Id), Arm(App(Tag, [Id]), Id)], Else(Int))"
+        );
+        assert_eq!(
+            shape("{ _x = f(1); g(2); 3 }"),
+            "Do([Assign(Ignore, App(Id, [Int])), Expr(App(Id, [Int]))], Int)"
+        );
+        assert_eq!(shape("{ x = 1; }"), "Do([Assign(Id, Int)])");
+    }
+
+    fn precedence(s: &str) -> u8 {
+        parse_expr(s).unwrap().precedence()
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(precedence("x -> x"), 0);
+        assert_eq!(precedence("x, y"), 1);
+        assert_eq!(precedence("case x of y = y end"), 3);
+        assert_eq!(precedence("{ x }"), 3);
+        assert_eq!(precedence("f(x)"), 4);
+        assert_eq!(precedence("1234"), Expr::ATOM_PRECEDENCE);
+        assert_eq!(precedence("(x, y)"), Expr::ATOM_PRECEDENCE);
+
+        let s = "..xs";
+        let ellipsis = Ellipsis {
+            span: Span::from(s),
+            id: Some(Span::new(s, 2, 4)),
+        };
+        assert_eq!(Expr::Expand(ellipsis).precedence(), 2);
+    }
+
+    #[test]
+    fn test_pattern_precedence() {
+        let precedence = |s| parse_pattern(s).unwrap().precedence();
+        assert_eq!(precedence(":some(x) | :ok(x)"), 0);
+        assert_eq!(precedence("x, y"), 1);
+        let s = "..xs";
+        let ellipsis = Ellipsis {
+            span: Span::from(s),
+            id: Some(Span::new(s, 2, 4)),
+        };
+        assert_eq!(Pattern::Collect(ellipsis).precedence(), 2);
+        assert_eq!(precedence("f(x)(y)"), 3);
+        for s in ["x", "_x", "1", ":x", "()", "(x, y)"] {
+            assert_eq!(precedence(s), Pattern::ATOM_PRECEDENCE, "{s}");
+        }
+    }
+
+    #[test]
+    fn test_is_atom() {
+        for s in ["1234", ":x", "x", "()", "(x -> x)", "(x, y)"] {
+            assert!(parse_expr(s).unwrap().is_atom(), "{s}");
+        }
+        for s in ["x -> x", "x, y", "f(x)", "{ x }", "case x of y = y end"] {
+            assert!(!parse_expr(s).unwrap().is_atom(), "{s}");
+        }
+    }
 }
@@ -0,0 +1,355 @@
+use crate::expr::{Ellipsis, Expr, Pattern, Statement};
+
+/// Render an expression on a single line with canonical spacing
+///
+/// Literals are copied from the source, so `1_000_000`, `255u8`, and `0xFF` keep their spelling.
+/// Parentheses are added wherever a child binds too loosely for its position, so that the output
+/// parses back to the same tree even if the tree was not built by the parser. Formatting that
+/// output again leaves it unchanged.
+///
+/// Comments are not part of the tree, so they are dropped: `{ # note\n x }` formats as `{ x }`.
+pub(crate) fn format_expr(e: &Expr) -> String {
+    let mut out = String::new();
+    expr(e, &mut out);
+    out
+}
+
+/// Render a pattern on a single line with canonical spacing, parenthesized as in `format_expr`
+pub(crate) fn format_pattern(p: &Pattern) -> String {
+    let mut out = String::new();
+    pattern(p, &mut out);
+    out
+}
+
+/// Precedence required of tuple items and arguments
+const ITEM: u8 = 2;
+
+/// Precedence required of the function in an application, which may itself be an application
+const CALLEE: u8 = 4;
+
+/// Precedence required of pattern alternatives
+const PATTERN_ALTERNATIVE: u8 = 1;
+
+/// Precedence required of pattern tuple items and arguments
+const PATTERN_ITEM: u8 = 2;
+
+/// Precedence required of the function in a pattern application
+const PATTERN_CALLEE: u8 = 3;
+
+fn expr(e: &Expr, out: &mut String) {
+    match e {
+        Expr::Int(span) | Expr::Float(span) => out.push_str(span.as_inner()),
+        Expr::Tag(_, name) => {
+            out.push(':');
+            out.push_str(name.as_inner());
+        }
+        Expr::Id(span) => out.push_str(span.as_inner()),
+        Expr::Expand(ellipsis) => self::ellipsis(ellipsis, out),
+        Expr::Tuple(_, xs) if xs.is_empty() => out.push_str("()"),
+        Expr::Tuple(_, xs) => {
+            items(xs, out);
+            if xs.len() == 1 {
+                out.push(',');
+            }
+        }
+        Expr::App(app) => {
+            at_least(&app.inner, CALLEE, out);
+            out.push('(');
+            items(&app.args, out);
+            out.push(')');
+        }
+        Expr::Case(case) => {
+            out.push_str("case ");
+            expr(&case.subject, out);
+            for arm in &case.arms {
+                out.push_str(" of ");
+                pattern(&arm.pattern, out);
+                out.push_str(" = ");
+                expr(&arm.expr, out);
+            }
+            if let Some(default) = &case.default {
+                out.push_str(" of else = ");
+                expr(&default.expr, out);
+            }
+            out.push_str(" end");
+        }
+        Expr::Paren(_, inner) => {
+            out.push('(');
+            expr(inner, out);
+            out.push(')');
+        }
+        Expr::Do(do_struct) => {
+            if do_struct.statements.is_empty() && do_struct.ret.is_none() {
+                out.push_str("{}");
+                return;
+            }
+            out.push('{');
+            for statement in &do_struct.statements {
+                out.push(' ');
+                match statement {
+                    Statement::Expr(e) => expr(e, out),
+                    Statement::Assign(assign) => {
+                        pattern(&assign.pattern, out);
+                        if let Some(ty) = assign.ty {
+                            out.push_str(" : ");
+                            out.push_str(ty.as_inner());
+                        }
+                        out.push_str(" = ");
+                        expr(&assign.expr, out);
+                    }
+                }
+                out.push(';');
+            }
+            if let Some(ret) = &do_struct.ret {
+                out.push(' ');
+                expr(ret, out);
+            }
+            out.push_str(" }");
+        }
+        Expr::Fn(_, param, body) => {
+            // x -> y -> z and x y -> z are the same tree, so print the shorter one
+            out.push_str(param.as_inner());
+            let mut body = &**body;
+            while let Expr::Fn(_, param, inner) = body {
+                out.push(' ');
+                out.push_str(param.as_inner());
+                body = inner;
+            }
+            out.push_str(" -> ");
+            expr(body, out);
+        }
+    }
+}
+
+/// Render `e`, parenthesized if it binds more loosely than `precedence`
+fn at_least(e: &Expr, precedence: u8, out: &mut String) {
+    if e.precedence() < precedence {
+        out.push('(');
+        expr(e, out);
+        out.push(')');
+    } else {
+        expr(e, out);
+    }
+}
+
+fn items(xs: &[Expr], out: &mut String) {
+    for (i, x) in xs.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        at_least(x, ITEM, out);
+    }
+}
+
+fn ellipsis(ellipsis: &Ellipsis, out: &mut String) {
+    out.push_str("..");
+    if let Some(id) = ellipsis.id {
+        out.push_str(id.as_inner());
+    }
+}
+
+fn pattern(p: &Pattern, out: &mut String) {
+    match p {
+        Pattern::Id(span) | Pattern::Ignore(span) | Pattern::Int(span) => {
+            out.push_str(span.as_inner())
+        }
+        Pattern::Tag(_, name) => {
+            out.push(':');
+            out.push_str(name.as_inner());
+        }
+        Pattern::Collect(e) => ellipsis(e, out),
+        Pattern::Tuple(_, xs) if xs.is_empty() => out.push_str("()"),
+        Pattern::Tuple(_, xs) => {
+            patterns(xs, out);
+            if xs.len() == 1 {
+                out.push(',');
+            }
+        }
+        Pattern::App(app) => {
+            pattern_at_least(&app.f, PATTERN_CALLEE, out);
+            out.push('(');
+            patterns(&app.xs, out);
+            out.push(')');
+        }
+        Pattern::Paren(_, inner) => {
+            out.push('(');
+            pattern(inner, out);
+            out.push(')');
+        }
+        Pattern::Or(_, xs) => {
+            for (i, x) in xs.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(" | ");
+                }
+                pattern_at_least(x, PATTERN_ALTERNATIVE, out);
+            }
+        }
+    }
+}
+
+/// Render `p`, parenthesized if it binds more loosely than `precedence`
+fn pattern_at_least(p: &Pattern, precedence: u8, out: &mut String) {
+    if p.precedence() < precedence {
+        out.push('(');
+        pattern(p, out);
+        out.push(')');
+    } else {
+        pattern(p, out);
+    }
+}
+
+fn patterns(xs: &[Pattern], out: &mut String) {
+    for (i, x) in xs.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        pattern_at_least(x, PATTERN_ITEM, out);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::expr::{shape, App, PatternApp};
+    use crate::parse::{parse_expr, parse_pattern};
+
+    fn format(s: &str) -> String {
+        format_expr(&parse_expr(s).unwrap())
+    }
+
+    #[test]
+    fn test_int_literals() {
+        assert_eq!(format("1_000_000"), "1_000_000");
+        assert_eq!(format("255u8"), "255u8");
+        assert_eq!(format("007"), "007");
+        assert_eq!(format("0xFF"), "0xFF");
+        assert_eq!(format("f(0xff_ffu16,0x1.8p3)"), "f(0xff_ffu16, 0x1.8p3)");
+        assert_eq!(format("f(1.5e3,2_0.0)"), "f(1.5e3, 2_0.0)");
+        assert_eq!(format("f(1_000,2_0u16)"), "f(1_000, 2_0u16)");
+        assert_eq!(
+            format("case x of 1_000 = 0_1 end"),
+            "case x of 1_000 = 0_1 end"
+        );
+    }
+
+    #[test]
+    fn test_spacing() {
+        assert_eq!(format("f( x ,y ,)( z )"), "f(x, y)(z)");
+        assert_eq!(format("( x , )"), "(x,)");
+        assert_eq!(format("x,"), "x,");
+        assert_eq!(format("( )"), "()");
+        assert_eq!(format(": tag"), ":tag");
+        assert_eq!(format("f(x, .. xs)"), "f(x, ..xs)");
+        assert_eq!(format("x -> y->f(x,y)"), "x y -> f(x, y)");
+        assert_eq!(format("{x=1;f(x);x}"), "{ x = 1; f(x); x }");
+        assert_eq!(format("{x=1;}"), "{ x = 1; }");
+        assert_eq!(format("{x:Int=1;}"), "{ x : Int = 1; }");
+        assert_eq!(format("{ }"), "{}");
+        assert_eq!(
+            format("case  x  of (a,..)=a of else=0 end"),
+            "case x of (a, ..) = a of else = 0 end"
+        );
+    }
+
+    #[test]
+    fn test_drops_comments() {
+        assert_eq!(format("{ # keep me\n x }"), "{ x }");
+        assert_eq!(
+            format("case x # subject\n of 1 = 2 # one\n end"),
+            "case x of 1 = 2 end"
+        );
+    }
+
+    #[test]
+    fn test_format_pattern() {
+        let format = |s| format_pattern(&parse_pattern(s).unwrap());
+        assert_eq!(format("(x,:some( y ),..rest)"), "(x, :some(y), ..rest)");
+        assert_eq!(format("x,(a,b),.."), "x, (a, b), ..");
+        assert_eq!(format("f( _x )( 1 )"), "f(_x)(1)");
+        assert_eq!(format("( )"), "()");
+        assert_eq!(format("(x,)"), "(x,)");
+        assert_eq!(format(":some( x )|:ok(x)"), ":some(x) | :ok(x)");
+        assert_eq!(format("(x,1)|(x ,2)"), "(x, 1) | (x, 2)");
+        assert_eq!(format("x, 1 | x, 2"), "x, 1 | x, 2");
+        assert_eq!(format("(:a | :b), x"), "(:a | :b), x");
+        assert_eq!(format("f((:a|:b))"), "f((:a | :b))");
+    }
+
+    #[test]
+    fn test_format_pattern_inserts_parens() {
+        // f((x, y)) with the parens removed from the tree
+        let p = parse_pattern("f((x, y))").unwrap();
+        let Pattern::App(app) = p else { panic!() };
+        let Pattern::Paren(_, inner) = &app.xs[0] else {
+            panic!()
+        };
+        let p = Pattern::App(PatternApp {
+            xs: vec![(**inner).clone()],
+            ..app
+        });
+        assert_eq!(format_pattern(&p), "f((x, y))");
+
+        // (:a | :b), x with the parens removed from the tree
+        let p = parse_pattern("(:a | :b), x").unwrap();
+        let Pattern::Tuple(span, mut xs) = p else {
+            panic!()
+        };
+        let Pattern::Paren(_, inner) = xs.remove(0) else {
+            panic!()
+        };
+        xs.insert(0, *inner);
+        assert_eq!(format_pattern(&Pattern::Tuple(span, xs)), "(:a | :b), x");
+    }
+
+    #[test]
+    fn test_idempotent() {
+        for s in [
+            "1_000, 255u8, :tag, x",
+            "(x,)",
+            "x,",
+            "f(x, ..ys,)(z)",
+            "(x -> x)(1)",
+            "f((x y -> g(x, y)), (a, b))",
+            "x -> y -> (x, y)",
+            "(case x of 1 = 2 end)(3)",
+            "case x of (a, ..rest) = a of :some(y) = y of _z = 0 of else = { } end",
+            "case x of 1 = case y of 2 = 3 end end",
+            "case x of :a | :b = 1 of (y, 1) | (y, 2), .. = y end",
+            "{\n  x : Int = 1;\n  (a, b) = (x, x);\n  f(a);\n  b\n}",
+            "{ f = n -> case n of 0 = 1 of m = m end; f(2) }",
+            "{ x = 1; }",
+            "(((x)))",
+        ] {
+            // Comments are dropped, so an input with one would not be reproduced faithfully
+            assert!(!s.contains('#'), "{s}");
+            let once = format(s);
+            assert_eq!(format(&once), once, "{s}");
+            assert_eq!(
+                shape(&parse_expr(&once).unwrap()),
+                shape(&parse_expr(s).unwrap()),
+                "{s}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_inserts_parens() {
+        // (x -> x)(1) with the parens removed from the tree
+        let e = parse_expr("(x -> x)(1)").unwrap();
+        let Expr::App(app) = e else { panic!() };
+        let e = Expr::App(App {
+            inner: Box::new(app.inner.strip_parens().clone()),
+            ..app
+        });
+        assert_eq!(format_expr(&e), "(x -> x)(1)");
+
+        // f((x, y)) with the parens removed from the tree
+        let e = parse_expr("f((x, y))").unwrap();
+        let Expr::App(app) = e else { panic!() };
+        let e = Expr::App(App {
+            args: vec![app.args[0].strip_parens().clone()],
+            ..app
+        });
+        assert_eq!(format_expr(&e), "f((x, y))");
+    }
+}
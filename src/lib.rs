@@ -0,0 +1,67 @@
+mod check;
+mod compile;
+#[allow(dead_code)]
+mod debug;
+mod env;
+mod error;
+mod eval;
+pub mod expr;
+#[allow(dead_code)]
+mod format;
+#[allow(dead_code)]
+mod lint;
+pub mod parse;
+pub mod span;
+
+use crate::{
+    check::check,
+    error::Error,
+    eval::{default_env, RuntimeError, RuntimeErrorKind, Value},
+    parse::parse_bytes,
+};
+use std::io::BufRead;
+
+/// Read expressions from stdin a line at a time, printing the value of each or its error
+pub fn repl() {
+    fn input() -> Vec<u8> {
+        let mut s = Vec::new();
+        std::io::stdin().lock().read_until(b'\n', &mut s).unwrap();
+        s
+    }
+
+    loop {
+        fn dec<'a>(args: &[Value<'a>]) -> Result<Value<'a>, RuntimeError<'a>> {
+            match args[0].as_int()?.checked_sub(1) {
+                Some(x) => Ok(Value::Int(x)),
+                None => Err(RuntimeError {
+                    kind: RuntimeErrorKind::ArithmeticOverflow,
+                    span: None,
+                }),
+            }
+        }
+        fn inc<'a>(args: &[Value<'a>]) -> Result<Value<'a>, RuntimeError<'a>> {
+            match args[0].as_int()?.checked_add(1) {
+                Some(x) => Ok(Value::Int(x)),
+                None => Err(RuntimeError {
+                    kind: RuntimeErrorKind::ArithmeticOverflow,
+                    span: None,
+                }),
+            }
+        }
+        let env = default_env()
+            .with_builtin("dec", 1, dec)
+            .with_builtin("inc", 1, inc);
+        let s = input();
+        if s.is_empty() {
+            break;
+        }
+        let result = parse_bytes(&s).map_err(Error::from).and_then(|e| {
+            check(&e)?;
+            Ok(e.eval_in(&env)?)
+        });
+        match result {
+            Ok(value) => println!("{value:?}"),
+            Err(err) => println!("{}", err.render(&String::from_utf8_lossy(&s))),
+        }
+    }
+}
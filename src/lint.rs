@@ -0,0 +1,257 @@
+use crate::{
+    expr::{Expr, Input, Pattern, Statement},
+    parse::parse_expr,
+};
+use nom::Slice;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum CommaPolicy {
+    /// Trailing commas are required in multi-line collections and forbidden in single-line ones
+    Vertical,
+    /// Trailing commas are always required
+    Always,
+    /// Trailing commas are always forbidden
+    Never,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Diagnostic<'a> {
+    pub(crate) span: Input<'a>,
+    pub(crate) message: &'static str,
+}
+
+/// Check every parenthesized tuple and argument list in `src` against `policy`
+///
+/// The comma of a 1-tuple such as `(x,)` is part of its syntax, so 1-tuples are exempt from every
+/// policy. Missing commas are reported at the closing paren, and unwanted commas at the comma itself.
+/// Source which fails to parse is reported as a single diagnostic.
+pub(crate) fn check_trailing_commas(src: &str, policy: CommaPolicy) -> Vec<Diagnostic<'_>> {
+    let mut out = Vec::new();
+    match parse_expr(src) {
+        Ok(e) => expr(&e, policy, &mut out),
+        Err(_) => out.push(Diagnostic {
+            span: Input::from(src),
+            message: "could not parse source",
+        }),
+    }
+    out
+}
+
+/// Check a collection spanning `span` from its opening to its closing paren
+fn collection<'a>(span: Input<'a>, len: usize, policy: CommaPolicy, out: &mut Vec<Diagnostic<'a>>) {
+    if len == 0 {
+        return;
+    }
+    let text = span.as_inner();
+    let close = text.len() - 1;
+    let body = trim_end(&text[..close]);
+    let has_comma = body.ends_with(',');
+    let wants_comma = match policy {
+        CommaPolicy::Vertical => text.contains('\n'),
+        CommaPolicy::Always => true,
+        CommaPolicy::Never => false,
+    };
+    if wants_comma && !has_comma {
+        out.push(Diagnostic {
+            span: span.slice(close..close + 1),
+            message: "missing trailing comma",
+        });
+    } else if !wants_comma && has_comma {
+        let comma = body.len() - 1;
+        out.push(Diagnostic {
+            span: span.slice(comma..comma + 1),
+            message: "unexpected trailing comma",
+        });
+    }
+}
+
+/// Strip trailing whitespace and comments
+fn trim_end(mut text: &str) -> &str {
+    loop {
+        text = text.trim_end();
+        let line_start = text.rfind('\n').map_or(0, |i| i + 1);
+        match text[line_start..].find('#') {
+            Some(i) => text = &text[..line_start + i],
+            None => return text,
+        }
+    }
+}
+
+fn expr<'a>(e: &Expr<'a>, policy: CommaPolicy, out: &mut Vec<Diagnostic<'a>>) {
+    match e {
+        Expr::Int(_) | Expr::Float(_) | Expr::Tag(..) | Expr::Id(_) | Expr::Expand(_) => {}
+        Expr::Tuple(_, xs) => xs.iter().for_each(|x| expr(x, policy, out)),
+        Expr::App(app) => {
+            expr(&app.inner, policy, out);
+            collection(app.arg_span, app.args.len(), policy, out);
+            app.args.iter().for_each(|x| expr(x, policy, out));
+        }
+        Expr::Case(case) => {
+            expr(&case.subject, policy, out);
+            for arm in &case.arms {
+                pattern(&arm.pattern, policy, out);
+                expr(&arm.expr, policy, out);
+            }
+            if let Some(default) = &case.default {
+                expr(&default.expr, policy, out);
+            }
+        }
+        Expr::Paren(span, inner) => {
+            if let Expr::Tuple(_, xs) = &**inner {
+                // A 1-tuple cannot drop its comma, whatever the policy
+                if xs.len() != 1 {
+                    collection(*span, xs.len(), policy, out);
+                }
+            }
+            expr(inner, policy, out);
+        }
+        Expr::Do(do_struct) => {
+            for statement in &do_struct.statements {
+                match statement {
+                    Statement::Expr(e) => expr(e, policy, out),
+                    Statement::Assign(assign) => {
+                        pattern(&assign.pattern, policy, out);
+                        expr(&assign.expr, policy, out);
+                    }
+                }
+            }
+            if let Some(ret) = &do_struct.ret {
+                expr(ret, policy, out);
+            }
+        }
+        Expr::Fn(_, _, body) => expr(body, policy, out),
+    }
+}
+
+fn pattern<'a>(p: &Pattern<'a>, policy: CommaPolicy, out: &mut Vec<Diagnostic<'a>>) {
+    match p {
+        Pattern::Id(_)
+        | Pattern::Ignore(_)
+        | Pattern::Int(_)
+        | Pattern::Tag(..)
+        | Pattern::Collect(_) => {}
+        Pattern::Tuple(_, xs) | Pattern::Or(_, xs) => {
+            xs.iter().for_each(|x| pattern(x, policy, out))
+        }
+        // Pattern arguments cannot have a trailing comma
+        Pattern::App(app) => {
+            pattern(&app.f, policy, out);
+            app.xs.iter().for_each(|x| pattern(x, policy, out));
+        }
+        Pattern::Paren(span, inner) => {
+            if let Pattern::Tuple(_, xs) = &**inner {
+                if xs.len() != 1 {
+                    collection(*span, xs.len(), policy, out);
+                }
+            }
+            pattern(inner, policy, out);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use nom::Offset;
+
+    /// Diagnostics as (byte offset, message) pairs
+    fn check(s: &str, policy: CommaPolicy) -> Vec<(usize, &'static str)> {
+        check_trailing_commas(s, policy)
+            .into_iter()
+            .map(|d| (Input::from(s).offset(&d.span), d.message))
+            .collect()
+    }
+
+    const MISSING: &str = "missing trailing comma";
+    const UNEXPECTED: &str = "unexpected trailing comma";
+
+    #[test]
+    fn test_vertical() {
+        let p = CommaPolicy::Vertical;
+        assert_eq!(check("(1, 2)", p), vec![]);
+        assert_eq!(check("(1, 2,)", p), vec![(5, UNEXPECTED)]);
+        assert_eq!(check("f(1, 2, )", p), vec![(6, UNEXPECTED)]);
+        assert_eq!(check("(\n  1,\n  2,\n)", p), vec![]);
+        assert_eq!(check("(\n  1,\n  2\n)", p), vec![(11, MISSING)]);
+        assert_eq!(check("f(\n  1\n)", p), vec![(7, MISSING)]);
+    }
+
+    #[test]
+    fn test_always() {
+        let p = CommaPolicy::Always;
+        assert_eq!(check("(1, 2,)", p), vec![]);
+        assert_eq!(check("(1, 2)", p), vec![(5, MISSING)]);
+        assert_eq!(check("f(1, 2)", p), vec![(6, MISSING)]);
+        assert_eq!(check("f()", p), vec![]);
+        assert_eq!(check("(\n  1,\n  2,\n)", p), vec![]);
+        assert_eq!(check("(\n  1,\n  2\n)", p), vec![(11, MISSING)]);
+    }
+
+    #[test]
+    fn test_never() {
+        let p = CommaPolicy::Never;
+        assert_eq!(check("(1, 2)", p), vec![]);
+        assert_eq!(check("(1,)", p), vec![]);
+        assert_eq!(check("(1, 2,)", p), vec![(5, UNEXPECTED)]);
+        assert_eq!(check("f(1,)", p), vec![(3, UNEXPECTED)]);
+        assert_eq!(check("(\n  1,\n  2,\n)", p), vec![(10, UNEXPECTED)]);
+        assert_eq!(check("(\n  1,\n  2\n)", p), vec![]);
+    }
+
+    #[test]
+    fn test_one_tuple() {
+        for p in [
+            CommaPolicy::Vertical,
+            CommaPolicy::Always,
+            CommaPolicy::Never,
+        ] {
+            assert_eq!(check("(x,)", p), vec![], "{p:?}");
+            assert_eq!(check("(\n  x,\n)", p), vec![], "{p:?}");
+            assert_eq!(check("{ (x,) = y; x }", p), vec![], "{p:?}");
+        }
+        // Argument lists are not tuples
+        assert_eq!(check("f(x,)", CommaPolicy::Vertical), vec![(3, UNEXPECTED)]);
+    }
+
+    #[test]
+    fn test_comments() {
+        assert_eq!(
+            check("(\n  1,\n  2, # two\n)", CommaPolicy::Vertical),
+            vec![]
+        );
+        assert_eq!(
+            check(
+                "(\n  1,\n  2 # two, three\n  # end\n)",
+                CommaPolicy::Vertical
+            ),
+            vec![(32, MISSING)],
+        );
+        assert_eq!(
+            check("f(1, # one\n)", CommaPolicy::Never),
+            vec![(3, UNEXPECTED)]
+        );
+    }
+
+    #[test]
+    fn test_nested() {
+        let s = "f((1, 2,), g(3,))";
+        assert_eq!(
+            check(s, CommaPolicy::Never),
+            vec![(7, UNEXPECTED), (14, UNEXPECTED)],
+        );
+
+        let s = "{ (x, y,) = (1, 2); case x of (a, b) = a end }";
+        assert_eq!(check(s, CommaPolicy::Never), vec![(7, UNEXPECTED)]);
+        assert_eq!(
+            check(s, CommaPolicy::Always),
+            vec![(17, MISSING), (35, MISSING)],
+        );
+    }
+
+    #[test]
+    fn test_unparseable() {
+        let diagnostics = check_trailing_commas("(1, ", CommaPolicy::Never);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "could not parse source");
+    }
+}
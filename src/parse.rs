@@ -1,39 +1,169 @@
 use crate::expr::{
-    App, Arm, Assign, Case, Do, Ellipsis, Expr, Input, Pattern, PatternApp, Statement,
+    App, Arm, Assign, Case, DefaultArm, Do, Ellipsis, Expr, Input, Pattern, PatternApp, Statement,
 };
 use crate::span::Span;
 
-use nom::combinator::consumed;
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::{alpha1, alphanumeric1, digit1, multispace0},
-    combinator::{cut, map, not, opt, value},
-    multi::{many0, many1, separated_list0},
+    character::complete::{
+        alpha1, alphanumeric1, digit1, hex_digit0, hex_digit1, multispace1, not_line_ending, one_of,
+    },
+    combinator::{cut, map, not, opt, recognize, verify},
+    error::{ErrorKind, ParseError as NomParseError},
+    multi::{many0, many1, separated_list1},
     sequence::{delimited, pair, preceded, terminated, tuple},
-    IResult,
+    Err, InputLength, Offset,
 };
 
-fn parse_int(s: Input) -> IResult<Input, Input> {
+/// Error produced by the parsers
+///
+/// `expected` describes what should have come next. It is only set where the grammar has no
+/// alternative, so such errors are failures which are not backtracked over.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyntaxError<I> {
+    pub input: I,
+    pub expected: Option<&'static str>,
+}
+
+impl<I> NomParseError<I> for SyntaxError<I> {
+    fn from_error_kind(input: I, _: ErrorKind) -> Self {
+        Self {
+            input,
+            expected: None,
+        }
+    }
+
+    fn append(_: I, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+pub type IResult<I, O> = nom::IResult<I, O, SyntaxError<I>>;
+
+/// Run `parser`, failing with a description of `what` was expected if it does not match
+fn expected<'a, O>(
+    what: &'static str,
+    mut parser: impl FnMut(Input<'a>) -> IResult<Input<'a>, O>,
+) -> impl FnMut(Input<'a>) -> IResult<Input<'a>, O> {
+    move |s| match parser(s) {
+        Err(Err::Error(_)) => Err(Err::Failure(SyntaxError {
+            input: s,
+            expected: Some(what),
+        })),
+        result => result,
+    }
+}
+
+/// ws = (space | '#' comment)*
+///
+/// Comments run to the end of the line.
+pub fn ws(s: Input) -> IResult<Input, Input> {
+    let (s1, _) = many0(alt((multispace1, preceded(tag("#"), not_line_ending))))(s)?;
+    Ok((s1, Span::between(s, s1)))
+}
+
+/// int_suffix = 'i8' | 'i16' | 'i32' | 'i64' | 'u8' | 'u16' | 'u32' | 'u64'
+fn parse_int_suffix(s: Input) -> IResult<Input, Input> {
+    alt((
+        tag("i8"),
+        tag("i16"),
+        tag("i32"),
+        tag("i64"),
+        tag("u8"),
+        tag("u16"),
+        tag("u32"),
+        tag("u64"),
+    ))(s)
+}
+
+/// int = ('0x' hex_digit+ ('_' hex_digit+)* | digits) int_suffix?
+pub fn parse_int(s: Input) -> IResult<Input, Input> {
     let (s1, _) = tuple((
-        digit1,
-        many0(pair(tag("_"), digit1)),
-        cut(not(pair(multispace0, tag("_")))),
+        alt((
+            recognize(tuple((
+                tag("0x"),
+                hex_digit1,
+                many0(pair(tag("_"), hex_digit1)),
+            ))),
+            parse_digits,
+        )),
+        opt(parse_int_suffix),
+        cut(not(pair(ws, tag("_")))),
     ))(s)?;
     Ok((s1, Span::between(s, s1)))
 }
 
-fn parse_kw(s: Input) -> IResult<Input, ()> {
-    value((), alt((tag("case"), tag("of"), tag("do"), tag("end"))))(s)
+/// digits = digit+ ('_' digit+)*
+fn parse_digits(s: Input) -> IResult<Input, Input> {
+    recognize(pair(digit1, many0(pair(tag("_"), digit1))))(s)
 }
 
-fn parse_id(s: Input) -> IResult<Input, Input> {
-    let (s1, _) = tuple((not(parse_kw), alpha1, many0(pair(tag("_"), alphanumeric1))))(s)?;
-    Ok((s1, Span::between(s, s1)))
+/// hex_float = '0x' hex_digit+ ('.' hex_digit*)? ('p' | 'P') ('+' | '-')? digit+
+///
+/// The binary exponent is required after a `.`, so `0x1.8` is an error rather than a float.
+/// Without a `.` it is optional, and `0xff` is left to be read as an int.
+fn parse_hex_float(s: Input) -> IResult<Input, Input> {
+    let exponent = || tuple((one_of("pP"), opt(one_of("+-")), digit1));
+    recognize(tuple((
+        tag("0x"),
+        expected("hex digits after '0x'", hex_digit1),
+        alt((
+            preceded(
+                pair(tag("."), hex_digit0),
+                expected("binary exponent 'p' in hex float", exponent()),
+            ),
+            exponent(),
+        )),
+    )))(s)
 }
 
-fn parse_tag(s: Input) -> IResult<Input, (Input, Input)> {
-    let (s1, span) = preceded(pair(tag(":"), multispace0), parse_id)(s)?;
+/// float = hex_float | digits '.' digits (('e' | 'E') ('+' | '-')? digit+)?
+///
+/// Digits are required on both sides of the `.`, so `1.` and `.5` are not floats.
+pub(crate) fn parse_float(s: Input) -> IResult<Input, Input> {
+    alt((
+        parse_hex_float,
+        recognize(tuple((
+            parse_digits,
+            tag("."),
+            parse_digits,
+            opt(tuple((one_of("eE"), opt(one_of("+-")), digit1))),
+        ))),
+    ))(s)
+}
+
+/// Words reserved by the language, which cannot be used as identifiers
+const KEYWORDS: &[&str] = &[
+    "and", "case", "def", "do", "else", "end", "false", "if", "in", "let", "not", "of", "or",
+    "then", "true", "type", "where",
+];
+
+/// word = alpha ('_' alnum)*
+///
+/// Keywords and identifiers are both words, so a keyword is only recognized as a whole word:
+/// `double` and `end_x` are identifiers.
+fn parse_word(s: Input) -> IResult<Input, Input> {
+    recognize(pair(alpha1, many0(pair(tag("_"), alphanumeric1))))(s)
+}
+
+/// The keyword `kw`, as a whole word
+fn keyword<'a>(kw: &'static str) -> impl FnMut(Input<'a>) -> IResult<Input<'a>, Input<'a>> {
+    verify(parse_word, move |word: &Input| word.as_inner() == kw)
+}
+
+/// id = word, where word is not a keyword
+pub fn parse_id(s: Input) -> IResult<Input, Input> {
+    verify(parse_word, |word: &Input| {
+        KEYWORDS.binary_search(&word.as_inner()).is_err()
+    })(s)
+}
+
+/// tag = ':' word
+///
+/// The `:` sets tags apart from keywords, so `:true` and `:if` are tags.
+pub fn parse_tag(s: Input) -> IResult<Input, (Input, Input)> {
+    let (s1, span) = preceded(pair(tag(":"), ws), parse_word)(s)?;
     Ok((s1, (Span::between(s, s1), span)))
 }
 
@@ -41,6 +171,10 @@ fn eint(s: Input) -> IResult<Input, Expr> {
     map(parse_int, Expr::Int)(s)
 }
 
+fn efloat(s: Input) -> IResult<Input, Expr> {
+    map(parse_float, Expr::Float)(s)
+}
+
 fn etag(s: Input) -> IResult<Input, Expr> {
     map(parse_tag, |(span1, span2)| Expr::Tag(span1, span2))(s)
 }
@@ -50,11 +184,11 @@ fn eid(s: Input) -> IResult<Input, Expr> {
 }
 
 fn eatom(s: Input) -> IResult<Input, Expr> {
-    alt((eunit, eid, etag, eint, eparen))(s)
+    alt((eunit, eid, etag, efloat, eint, eparen))(s)
 }
 
 fn parse_ellipsis(s: Input) -> IResult<Input, Ellipsis> {
-    let (s1, id) = preceded(tag(".."), preceded(multispace0, opt(parse_id)))(s)?;
+    let (s1, id) = preceded(tag(".."), preceded(ws, opt(parse_id)))(s)?;
     let span = Span::between(s, s1);
     Ok((s1, Ellipsis { span, id }))
 }
@@ -67,13 +201,10 @@ fn eapp(s: Input) -> IResult<Input, Expr> {
     /// '(' ws (eitem ws ',' ws)* eitem? ws ')'
     fn args(s: Input) -> IResult<Input, (Input, Vec<Expr>)> {
         let (s1, args) = delimited(
-            pair(tag("("), multispace0),
+            pair(tag("("), ws),
             map(
                 pair(
-                    many0(terminated(
-                        eitem,
-                        tuple((multispace0, tag(","), multispace0)),
-                    )),
+                    many0(terminated(eitem, tuple((ws, tag(","), ws)))),
                     opt(eitem),
                 ),
                 |(mut xs, x)| {
@@ -83,13 +214,13 @@ fn eapp(s: Input) -> IResult<Input, Expr> {
                     xs
                 },
             ),
-            pair(multispace0, tag(")")),
+            cut(pair(ws, tag(")"))),
         )(s)?;
         let span = Span::between(s, s1);
         Ok((s1, (span, args)))
     }
 
-    let (s1, (mut f, xs)) = pair(eatom, many0(preceded(multispace0, args)))(s)?;
+    let (s1, (mut f, xs)) = pair(eatom, many0(preceded(ws, args)))(s)?;
     for (arg_span, args) in xs {
         let span = Span::to(s, arg_span);
         let inner = Box::new(f);
@@ -105,30 +236,45 @@ fn eapp(s: Input) -> IResult<Input, Expr> {
 
 /// eunit = '(' ')'
 fn eunit(s: Input) -> IResult<Input, Expr> {
-    let (s1, _) = tuple((tag("("), multispace0, tag(")")))(s)?;
+    let (s1, _) = tuple((tag("("), ws, tag(")")))(s)?;
     Ok((s1, Expr::Tuple(Span::between(s, s1), vec![])))
 }
 
+/// (item ',')+ item?
+///
+/// Whitespace after a trailing comma is left unconsumed.
+fn tuple_items<'a, O>(
+    item: impl FnMut(Input<'a>) -> IResult<Input<'a>, O> + Copy,
+) -> impl FnMut(Input<'a>) -> IResult<Input<'a>, Vec<O>> {
+    move |s| {
+        let (s1, (x, mut xs, trailing)) = tuple((
+            item,
+            many0(preceded(tuple((ws, tag(","), ws)), item)),
+            opt(pair(ws, tag(","))),
+        ))(s)?;
+        // A single item is only a tuple with a trailing comma
+        if xs.is_empty() && trailing.is_none() {
+            return Err(Err::Error(SyntaxError::from_error_kind(
+                s,
+                ErrorKind::Many1,
+            )));
+        }
+        xs.insert(0, x);
+        Ok((s1, xs))
+    }
+}
+
 /// etuple = (eitem ',')+ eitem?
 fn etuple(s: Input) -> IResult<Input, Expr> {
-    let (s1, (mut xs, x)) = pair(
-        many1(terminated(
-            eitem,
-            tuple((multispace0, tag(","), multispace0)),
-        )),
-        opt(preceded(multispace0, eitem)),
-    )(s)?;
-    if let Some(x) = x {
-        xs.push(x);
-    }
+    let (s1, xs) = tuple_items(eitem)(s)?;
     let span = Span::between(s, s1);
     Ok((s1, Expr::Tuple(span, xs)))
 }
 
 fn arm(s: Input) -> IResult<Input, Arm> {
     let (s1, (pattern, expr)) = pair(
-        preceded(terminated(tag("of"), multispace0), pattern),
-        preceded(tuple((multispace0, tag("="), multispace0)), expr),
+        preceded(terminated(keyword("of"), ws), pattern),
+        preceded(tuple((ws, tag("="), ws)), expr),
     )(s)?;
     let span = Span::between(s, s1);
     Ok((
@@ -141,14 +287,31 @@ fn arm(s: Input) -> IResult<Input, Arm> {
     ))
 }
 
+/// default_arm = 'of' 'else' '=' expr
+fn default_arm(s: Input) -> IResult<Input, DefaultArm> {
+    let (s1, expr) = preceded(
+        tuple((keyword("of"), ws, keyword("else"), ws, tag("="), ws)),
+        expr,
+    )(s)?;
+    let span = Span::between(s, s1);
+    Ok((s1, DefaultArm { span, expr }))
+}
+
+/// case = 'case' expr arm* default_arm? 'end'
+///
+/// The subject is required, so `case of` is an error rather than a match on unit.
 fn ecase(s: Input) -> IResult<Input, Expr> {
-    let (s1, (subject, arms)) = pair(
-        preceded(pair(tag("case"), multispace0), expr),
+    let (s1, (subject, arms, default)) = tuple((
+        preceded(
+            pair(keyword("case"), ws),
+            expected("subject expression after 'case'", expr),
+        ),
+        many0(preceded(ws, arm)),
         terminated(
-            many0(preceded(multispace0, arm)),
-            pair(multispace0, tag("end")),
+            opt(preceded(ws, map(default_arm, Box::new))),
+            cut(pair(ws, keyword("end"))),
         ),
-    )(s)?;
+    ))(s)?;
     let span = Span::between(s, s1);
     let subject = Box::new(subject);
     Ok((
@@ -157,41 +320,51 @@ fn ecase(s: Input) -> IResult<Input, Expr> {
             span,
             subject,
             arms,
+            default,
         }),
     ))
 }
 
+/// assign = pattern (':' id)? '=' expr
 fn assign(s: Input) -> IResult<Input, Statement> {
-    let (s1, (pattern, expr)) = pair(
+    let (s1, (pattern, ty, expr)) = tuple((
         pattern,
-        preceded(tuple((multispace0, tag("="), multispace0)), expr),
-    )(s)?;
+        opt(preceded(tuple((ws, tag(":"), ws)), parse_id)),
+        preceded(tuple((ws, tag("="), ws)), expr),
+    ))(s)?;
     let span = Span::between(s, s1);
     Ok((
         s1,
         Statement::Assign(Assign {
             span,
             pattern,
+            ty,
             expr,
         }),
     ))
 }
 
-fn statement(s: Input) -> IResult<Input, Statement> {
+pub fn statement(s: Input) -> IResult<Input, Statement> {
     alt((assign, map(expr, Statement::Expr)))(s)
 }
 
 fn edo(s: Input) -> IResult<Input, Expr> {
+    /// statement ws ';' ws
+    fn terminated_statement(s: Input) -> IResult<Input, Statement> {
+        let mut semicolon = tuple((ws, tag(";"), ws));
+        let (s1, statement) = statement(s)?;
+        // An expression may instead be the return value, but an assignment must be terminated
+        let (s1, _) = match statement {
+            Statement::Assign(_) => cut(semicolon)(s1)?,
+            Statement::Expr(_) => semicolon(s1)?,
+        };
+        Ok((s1, statement))
+    }
+
     let (s1, (statements, ret)) = delimited(
-        pair(tag("{"), multispace0),
-        pair(
-            many0(terminated(
-                statement,
-                tuple((multispace0, tag(";"), multispace0)),
-            )),
-            opt(map(expr, Box::new)),
-        ),
-        pair(multispace0, tag("}")),
+        pair(tag("{"), ws),
+        pair(many0(terminated_statement), opt(map(expr, Box::new))),
+        cut(pair(ws, tag("}"))),
     )(s)?;
     let span = Span::between(s, s1);
     Ok((
@@ -205,41 +378,185 @@ fn edo(s: Input) -> IResult<Input, Expr> {
 }
 
 fn eparen(s: Input) -> IResult<Input, Expr> {
-    let (s1, inner) = delimited(
-        pair(tag("("), multispace0),
-        expr,
-        pair(multispace0, tag(")")),
-    )(s)?;
+    let (s1, inner) = delimited(pair(tag("("), ws), expr, cut(pair(ws, tag(")"))))(s)?;
     let span = Span::between(s, s1);
     let expr = Expr::Paren(span, Box::new(inner));
     Ok((s1, expr))
 }
 
-/// fn = param fn | param ws '->' ws expr
+/// fn = (id ws)+ '->' ws expr
+///
+/// `x y -> body` is the same tree as `x -> y -> body`.
 fn efn(s: Input) -> IResult<Input, Expr> {
-    map(
-        consumed(alt((
-            pair(parse_id, preceded(multispace0, map(efn, Box::new))),
-            pair(
-                parse_id,
-                preceded(
-                    tuple((multispace0, tag("->"), multispace0)),
-                    map(expr, Box::new),
-                ),
-            ),
-        ))),
-        |(span, (param, body))| Expr::Fn(span, param, body),
-    )(s)
+    let (s1, (params, body)) = pair(
+        terminated(many1(terminated(parse_id, ws)), tag("->")),
+        preceded(ws, expected("expression after '->'", expr)),
+    )(s)?;
+    let body = params.into_iter().rev().fold(body, |body, param| {
+        Expr::Fn(Span::to(param, body.span()), param, Box::new(body))
+    });
+    Ok((s1, body))
 }
 
 fn eother(s: Input) -> IResult<Input, Expr> {
     alt((eapp, ecase, edo))(s)
 }
 
-pub(crate) fn expr(s: Input) -> IResult<Input, Expr> {
+pub fn expr(s: Input) -> IResult<Input, Expr> {
     alt((efn, etuple, eother))(s)
 }
 
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum ParseError<'a> {
+    /// The source is not valid UTF-8 from the given byte offset on
+    InvalidUtf8(usize),
+    /// Parsing failed at the start of the given input
+    Unexpected(Input<'a>),
+    /// The given input does not start with what was expected, such as "expression after '->'"
+    Expected(&'static str, Input<'a>),
+}
+
+/// Run `parser` over the whole of `src`, allowing surrounding whitespace
+fn parse_all<'a, O>(
+    src: &'a str,
+    parser: impl FnMut(Input<'a>) -> IResult<Input<'a>, O>,
+) -> Result<O, ParseError<'a>> {
+    match delimited(ws, parser, ws)(Span::from(src)) {
+        Ok((rest, x)) if rest.input_len() == 0 => Ok(x),
+        Ok((rest, _)) => Err(ParseError::Unexpected(rest)),
+        Err(Err::Error(e) | Err::Failure(e)) => Err(match e.expected {
+            Some(what) => ParseError::Expected(what, e.input),
+            None => ParseError::Unexpected(e.input),
+        }),
+        Err(Err::Incomplete(_)) => Err(ParseError::Unexpected(Span::end(src))),
+    }
+}
+
+/// Parse the whole of `src` as a single expression, allowing surrounding whitespace
+pub(crate) fn parse_expr(src: &str) -> Result<Expr<'_>, ParseError<'_>> {
+    parse_all(src, expr)
+}
+
+/// Like `parse_expr`, but for source which may not be valid UTF-8
+pub(crate) fn parse_bytes(bytes: &[u8]) -> Result<Expr<'_>, ParseError<'_>> {
+    let src = std::str::from_utf8(bytes).map_err(|e| ParseError::InvalidUtf8(e.valid_up_to()))?;
+    parse_expr(src)
+}
+
+/// Like `parse_expr`, but reporting every syntax error instead of only the first
+///
+/// After an error inside a do-block, the statement containing it is skipped up to its `;`, or up
+/// to the `}` closing the block, and the rest is parsed again. Brackets are counted while skipping,
+/// so that after an error in `f(g(x y), z);` parsing resumes after the `;` rather than at the `)`
+/// closing `g`. An error outside any do-block ends the parse, as does an error repeating the last
+/// one, such as a missing `}` which blanking out the last statement does not fix.
+///
+/// Each error costs a parse of the whole source, so this is O(n * errors), and the skipping is done
+/// by a separate scan of the text rather than inside the grammar. Both are fine for reporting the
+/// handful of errors a person fixes at once; recovering inside `edo` would avoid them if needed.
+#[allow(dead_code)]
+pub(crate) fn parse_expr_recovering(src: &str) -> Result<Expr<'_>, Vec<ParseError<'_>>> {
+    let mut errors = match parse_expr(src) {
+        Ok(e) => return Ok(e),
+        Err(err) => vec![err],
+    };
+    // Skipped statements are blanked out, which keeps the offsets of everything else
+    let mut text = src.to_string();
+    while let Some(range) = errors
+        .last()
+        .and_then(|err| failed_statement(&text, err.offset(src)?))
+    {
+        if text[range.clone()].trim().is_empty() {
+            break;
+        }
+        text.replace_range(range.clone(), &" ".repeat(range.len()));
+        let err = match parse_expr(&text) {
+            Ok(_) => break,
+            Err(err) => err.rebase(&text, src),
+        };
+        if errors.last().is_some_and(|last| last.repeats(&err, src)) {
+            break;
+        }
+        errors.push(err);
+    }
+    Err(errors)
+}
+
+/// The byte range of the statement containing `offset`, including its `;`, if it is in a do-block
+fn failed_statement(text: &str, offset: usize) -> Option<std::ops::Range<usize>> {
+    // Open brackets before the error, each with the start of its current statement
+    let mut open: Vec<(char, usize)> = Vec::new();
+    let mut chars = text[..offset].char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '#' => {
+                chars.by_ref().find(|&(_, c)| c == '\n');
+            }
+            '(' | '{' => open.push((c, i + 1)),
+            ')' | '}' => {
+                open.pop();
+            }
+            ';' => {
+                if let Some((_, start)) = open.last_mut() {
+                    *start = i + 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    let &(_, start) = open.iter().rev().find(|(c, _)| *c == '{')?;
+
+    // Skip to the end of the statement, past any brackets opened within it
+    let mut depth = 0;
+    let mut chars = text[start..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '#' => {
+                chars.by_ref().find(|&(_, c)| c == '\n');
+            }
+            '(' | '{' => depth += 1,
+            ')' | '}' if depth == 0 => return Some(start..start + i),
+            ')' | '}' => depth -= 1,
+            ';' if depth == 0 => return Some(start..start + i + 1),
+            _ => {}
+        }
+    }
+    Some(start..text.len())
+}
+
+impl<'a> ParseError<'a> {
+    /// Byte offset of the error in `src`, which it must have been produced from
+    fn offset(&self, src: &str) -> Option<usize> {
+        match self {
+            Self::InvalidUtf8(_) => None,
+            Self::Unexpected(span) | Self::Expected(_, span) => Some(Span::from(src).offset(span)),
+        }
+    }
+
+    /// Whether `other` is the same kind of error at the same offset in `src`
+    fn repeats(&self, other: &Self, src: &str) -> bool {
+        let same_kind = match (self, other) {
+            (Self::Unexpected(_), Self::Unexpected(_)) => true,
+            (Self::Expected(x, _), Self::Expected(y, _)) => x == y,
+            _ => false,
+        };
+        same_kind && self.offset(src) == other.offset(src)
+    }
+
+    /// The same error in `src`, given that it was produced from `text` with the same offsets
+    fn rebase<'b>(&self, text: &str, src: &'b str) -> ParseError<'b> {
+        let rebase = |span: &Input| {
+            let start = Span::from(text).offset(span);
+            Span::new(src, start, start + span.input_len())
+        };
+        match self {
+            Self::InvalidUtf8(offset) => ParseError::InvalidUtf8(*offset),
+            Self::Unexpected(span) => ParseError::Unexpected(rebase(span)),
+            Self::Expected(what, span) => ParseError::Expected(what, rebase(span)),
+        }
+    }
+}
+
 fn pint(s: Input) -> IResult<Input, Pattern> {
     map(parse_int, Pattern::Int)(s)
 }
@@ -260,18 +577,14 @@ fn pignore(s: Input) -> IResult<Input, Pattern> {
 }
 
 fn punit(s: Input) -> IResult<Input, Pattern> {
-    let (s1, _) = tuple((tag("("), multispace0, tag(")")))(s)?;
+    let (s1, _) = tuple((tag("("), ws, tag(")")))(s)?;
     let span = Span::between(s, s1);
     let pat = Pattern::Tuple(span, vec![]);
     Ok((s1, pat))
 }
 
 fn pparen(s: Input) -> IResult<Input, Pattern> {
-    let (s1, inner) = delimited(
-        pair(tag("("), multispace0),
-        pattern,
-        pair(multispace0, tag(")")),
-    )(s)?;
+    let (s1, inner) = delimited(pair(tag("("), ws), pattern, pair(ws, tag(")")))(s)?;
     let span = Span::between(s, s1);
     let pat = Pattern::Paren(span, Box::new(inner));
     Ok((s1, pat))
@@ -285,33 +598,23 @@ fn pitem(s: Input) -> IResult<Input, Pattern> {
     alt((map(parse_ellipsis, Pattern::Collect), pother))(s)
 }
 
+/// ptuple = (pitem ',')+ pitem?
 fn ptuple(s: Input) -> IResult<Input, Pattern> {
-    let (s1, xs) = map(
-        pair(
-            many1(terminated(
-                pitem,
-                tuple((multispace0, tag(","), multispace0)),
-            )),
-            opt(pitem),
-        ),
-        |(mut xs, x)| {
-            if let Some(x) = x {
-                xs.push(x);
-            }
-            xs
-        },
-    )(s)?;
+    let (s1, xs) = tuple_items(pitem)(s)?;
     let span = Span::between(s, s1);
     let pat = Pattern::Tuple(span, xs);
     Ok((s1, pat))
 }
 
 fn papp(s: Input) -> IResult<Input, Pattern> {
+    /// '(' ws pitem (ws ',' ws pitem)* ws ')'
+    ///
+    /// At least one argument is required: nullary tags are matched as `:none`, not `:none()`.
     fn args(s: Input) -> IResult<Input, (Input, Vec<Pattern>)> {
         let (s1, xs) = delimited(
-            pair(tag("("), multispace0),
-            separated_list0(tuple((multispace0, tag(","), multispace0)), pitem),
-            pair(multispace0, tag(")")),
+            pair(tag("("), ws),
+            separated_list1(tuple((ws, tag(","), ws)), pitem),
+            pair(ws, tag(")")),
         )(s)?;
         let span = Span::between(s, s1);
         Ok((s1, (span, xs)))
@@ -334,13 +637,79 @@ fn pother(s: Input) -> IResult<Input, Pattern> {
     alt((papp,))(s)
 }
 
-fn pattern(s: Input) -> IResult<Input, Pattern> {
-    alt((ptuple, pother))(s)
+/// pattern = palt ('|' palt)*, where palt = ptuple | pother
+///
+/// Every alternative must bind the same names, so that the body of an arm can use them whichever
+/// alternative matched.
+pub fn pattern(s: Input) -> IResult<Input, Pattern> {
+    let (mut s1, x) = alt((ptuple, pother))(s)?;
+    let names = x.names();
+    let mut xs = vec![x];
+    while let Ok((s2, _)) = tuple((ws, tag("|"), ws))(s1) {
+        let (s3, x) = expected("pattern after '|'", alt((ptuple, pother)))(s2)?;
+        if x.names() != names {
+            return Err(Err::Failure(SyntaxError {
+                input: s2,
+                expected: Some("the same names as the first alternative"),
+            }));
+        }
+        xs.push(x);
+        s1 = s3;
+    }
+    if xs.len() == 1 {
+        return Ok((s1, xs.remove(0)));
+    }
+    Ok((s1, Pattern::Or(Span::between(s, s1), xs)))
+}
+
+/// Parse the whole of `src` as a single pattern, allowing surrounding whitespace
+#[allow(dead_code)]
+pub(crate) fn parse_pattern(src: &str) -> Result<Pattern<'_>, ParseError<'_>> {
+    parse_all(src, pattern)
+}
+
+/// Parsers for embedding the grammar in a larger language.
+///
+/// The following are stable: each takes an `Input` and returns an `IResult` with a `SyntaxError`,
+/// and apart from `ws` none consume leading or trailing whitespace.
+///
+/// - `ws`: whitespace and comments, possibly none
+/// - `expr`: a full expression, including functions and bare tuples
+/// - `pattern`: a full pattern, including bare tuples
+/// - `statement`: an assignment or expression, without the trailing `;`
+/// - `parse_id`: an identifier which is not a keyword
+/// - `parse_int`: an integer literal, with optional suffix
+/// - `parse_tag`: a tag, returning the whole span and the span of the name
+///
+/// Any other parser in this module may change without notice.
+///
+/// For example, a host language with `return expr` statements:
+///
+/// ```
+/// use fast_rs::{expr::{Expr, Input}, parse::combinators::{expr, IResult}, span::Span};
+/// use nom::{bytes::complete::tag, character::complete::multispace1, sequence::{pair, preceded}};
+///
+/// fn ret(s: Input) -> IResult<Input, Expr> {
+///     preceded(pair(tag("return"), multispace1), expr)(s)
+/// }
+///
+/// let s = "return f(x)";
+/// let (rest, e) = ret(Span::from(s)).unwrap();
+/// assert_eq!(rest, Span::end(s));
+/// assert!(matches!(e, Expr::App(_)));
+///
+/// assert!(ret(Span::from("returnf(x)")).is_err());
+/// ```
+pub mod combinators {
+    pub use super::{
+        expr, parse_id, parse_int, parse_tag, pattern, statement, ws, IResult, SyntaxError,
+    };
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::{expr::is_grouping_paren, format::format_expr};
 
     macro_rules! assert_err {
         ($e: expr) => {
@@ -380,6 +749,61 @@ mod test {
         );
 
         assert_err!(eint(Span::from(" 1234")));
+
+        let s = "1_000u16";
+        let span = Span::from(s);
+        assert_eq!(eint(span), Ok((Span::end(s), Expr::Int(span))));
+
+        let s = "12u7";
+        assert_eq!(
+            eint(Span::from(s)),
+            Ok((Span::new(s, 2, 4), Expr::Int(Span::new(s, 0, 2)))),
+        );
+
+        for s in ["0xFF", "0xff_ffu16", "0x1e3", "0x7fi8"] {
+            assert_eq!(parse_expr(s), Ok(Expr::Int(Span::from(s))), "{s}");
+        }
+        assert_err!(parse_expr("0xg"));
+        assert_err!(parse_expr("0xff_"));
+    }
+
+    #[test]
+    fn test_efloat() {
+        for s in ["1.5", "0.0", "1_000.000_1", "1.5e3", "1.5E-3", "2.0e+10"] {
+            assert_eq!(parse_expr(s), Ok(Expr::Float(Span::from(s))), "{s}");
+        }
+        // Digits are required on both sides of the point
+        for s in ["1.", ".5", "1.e3", "1.5e"] {
+            assert_err!(parse_expr(s));
+        }
+        assert!(matches!(parse_expr("1"), Ok(Expr::Int(_))));
+        assert!(matches!(parse_expr("f(1.5, ..xs)"), Ok(Expr::App(_))));
+    }
+
+    #[test]
+    fn test_hex_float() {
+        for s in ["0x1.8p3", "0x1p-2", "0xA.Fp+1", "0x1.P0", "0xffP10"] {
+            assert_eq!(parse_expr(s), Ok(Expr::Float(Span::from(s))), "{s}");
+        }
+        // `e` is a hex digit, so it cannot stand in for `p`
+        for s in ["0x1.8", "0x1.", "0x1.8e3"] {
+            assert_eq!(
+                parse_expr(s),
+                Err(ParseError::Expected(
+                    "binary exponent 'p' in hex float",
+                    Span::end(s)
+                )),
+                "{s}"
+            );
+        }
+        let s = "0x.8p1";
+        assert_eq!(
+            parse_expr(s),
+            Err(ParseError::Expected(
+                "hex digits after '0x'",
+                Span::new(s, 2, 6)
+            ))
+        );
     }
 
     #[test]
@@ -396,6 +820,21 @@ mod test {
 
         let s = " : xyz";
         assert_err!(etag(Span::from(s)));
+
+        // Keywords may be tag names
+        let s = ":true";
+        assert_eq!(
+            parse_expr(s),
+            Ok(Expr::Tag(Span::from(s), Span::new(s, 1, 5)))
+        );
+        for s in [":false", ":type", ":if", ":in", ":end"] {
+            assert!(matches!(parse_expr(s), Ok(Expr::Tag(..))), "{s}");
+            assert!(matches!(parse_pattern(s), Ok(Pattern::Tag(..))), "{s}");
+        }
+        assert!(matches!(
+            parse_expr("case x of :true = 1 of :false = 0 end"),
+            Ok(Expr::Case(_))
+        ));
     }
 
     #[test]
@@ -408,6 +847,44 @@ mod test {
         assert_err!(eid("   xyz".into()));
     }
 
+    #[test]
+    fn test_keywords() {
+        // Sorted for binary search
+        assert!(KEYWORDS.windows(2).all(|w| w[0] < w[1]));
+
+        for kw in [
+            "case", "of", "do", "end", "if", "then", "else", "let", "in", "where", "type", "def",
+            "true", "false", "and", "or", "not",
+        ] {
+            assert!(KEYWORDS.contains(&kw), "{kw}");
+            assert_err!(parse_id(Span::from(kw)));
+            assert_eq!(
+                keyword(kw)(Span::from(kw)),
+                Ok((Span::end(kw), Span::from(kw)))
+            );
+        }
+        assert_eq!(KEYWORDS.len(), 17);
+    }
+
+    #[test]
+    fn test_keyword_boundary() {
+        for s in [
+            "double", "ending", "offset", "iffy", "truey", "notice", "android", "cased", "do_x",
+            "end_1", "Case", "whereas", "types",
+        ] {
+            assert_eq!(
+                parse_id(Span::from(s)),
+                Ok((Span::end(s), Span::from(s))),
+                "{s}"
+            );
+        }
+        assert_err!(keyword("do")(Span::from("double")));
+        assert_err!(keyword("end")(Span::from("end_x")));
+
+        let s = "case offset of ending = double(ending) end";
+        assert!(matches!(parse_expr(s), Ok(Expr::Case(_))));
+    }
+
     #[test]
     fn test_eparen() {
         let s = "(  1234)";
@@ -418,6 +895,143 @@ mod test {
         assert_err!(eparen(Span::from("  (  1234)")));
     }
 
+    #[test]
+    fn test_tuple_arity() {
+        fn arity(s: &str) -> usize {
+            let (rest, e) = expr(s.into()).unwrap();
+            assert_eq!(rest, Span::end(s));
+            match e.strip_parens() {
+                Expr::Tuple(_, xs) => xs.len(),
+                e => panic!("expected a tuple: {e:?}"),
+            }
+        }
+
+        assert_eq!(arity("()"), 0);
+        assert_eq!(arity("(x,)"), 1);
+        assert_eq!(arity("x,"), 1);
+        assert_eq!(arity("(x, y)"), 2);
+        assert_eq!(arity("x, y,"), 2);
+        assert_eq!(arity("(x, y, z)"), 3);
+        assert_eq!(arity("((x, y, z,))"), 3);
+    }
+
+    #[test]
+    fn test_grouping_paren() {
+        let paren = |s| expr(Span::from(s)).unwrap().1;
+
+        assert!(is_grouping_paren(&paren("(x)")));
+        assert!(is_grouping_paren(&paren("((x, y))")));
+        assert!(!is_grouping_paren(&paren("(x,)")));
+        assert!(!is_grouping_paren(&paren("(x, y)")));
+        assert!(!is_grouping_paren(&paren("()")));
+        assert!(!is_grouping_paren(&paren("x")));
+
+        let s = "((x))";
+        assert_eq!(paren(s).strip_parens(), &Expr::Id(Span::new(s, 2, 3)));
+    }
+
+    #[test]
+    fn test_parse_expr() {
+        let s = " f(x) ";
+        assert_eq!(
+            parse_expr(s),
+            Ok(Expr::App(App {
+                span: Span::new(s, 1, 5),
+                inner: Box::new(Expr::Id(Span::new(s, 1, 2))),
+                arg_span: Span::new(s, 2, 5),
+                args: vec![Expr::Id(Span::new(s, 3, 4))],
+            })),
+        );
+
+        let s = "f(x) )";
+        assert_eq!(
+            parse_expr(s),
+            Err(ParseError::Unexpected(Span::new(s, 5, 6)))
+        );
+    }
+
+    #[test]
+    fn test_leftover() {
+        /// The unexpected input as a byte offset and the text left from there
+        fn leftover(s: &str) -> (usize, &str) {
+            match parse_expr(s) {
+                Err(ParseError::Unexpected(rest)) => (Span::from(s).offset(&rest), rest.as_inner()),
+                x => panic!("expected leftover input: {x:?}"),
+            }
+        }
+
+        assert_eq!(leftover("case x of x = x end extra"), (20, "extra"));
+        assert_eq!(leftover("{ x } extra"), (6, "extra"));
+        assert_eq!(leftover("x -> { x } extra"), (11, "extra"));
+
+        // Nested in parens, arguments, statements, and subjects
+        assert_eq!(leftover("f(case x of x = x end extra)"), (22, "extra)"));
+        assert_eq!(
+            leftover("(case x of x = x end extra, 1)"),
+            (21, "extra, 1)")
+        );
+        assert_eq!(leftover("(x, { x } extra)"), (10, "extra)"));
+        assert_eq!(
+            leftover("{ y = case x of x = x end extra; y }"),
+            (26, "extra; y }")
+        );
+        assert_eq!(leftover("{ y = { x } extra; y }"), (12, "extra; y }"));
+        assert_eq!(leftover("{ case x of x = x end extra }"), (22, "extra }"));
+        assert_eq!(
+            leftover("case case x of x = x end extra of y = y end"),
+            (25, "extra of y = y end")
+        );
+    }
+
+    #[test]
+    fn test_recovery() {
+        /// The offset of each error
+        fn errors(s: &str) -> Vec<usize> {
+            parse_expr_recovering(s)
+                .unwrap_err()
+                .iter()
+                .map(|err| err.offset(s).unwrap())
+                .collect()
+        }
+
+        let s = "{ x = 1; x }";
+        assert_eq!(parse_expr_recovering(s), parse_expr(s).map_err(|e| vec![e]));
+
+        // Resume after the `;` ending the call, not at the `)` closing `g`
+        let s = "{ f(g(x y), z); h(1 2); w }";
+        assert_eq!(errors(s), vec![8, 20]);
+        assert_eq!(
+            parse_expr_recovering(s).unwrap_err()[0],
+            parse_expr(s).unwrap_err()
+        );
+
+        // A block nested in the failed statement is skipped with it
+        assert_eq!(errors("{ f(x y, { a; b }); z w; v }"), vec![6, 22]);
+        // An error in a nested block only skips the statement in that block
+        assert_eq!(errors("{ x = { a b; c }; y z; w }"), vec![10, 20]);
+        // The return value is skipped up to the end of the block
+        assert_eq!(errors("{ x y; (y z) }"), vec![4, 10]);
+        // Brackets in comments are not counted
+        assert_eq!(errors("{ f(x y # (\n); g(1 2); w }"), vec![6, 19]);
+        // There is nothing to skip to outside a block
+        assert_eq!(errors("f(x y)"), vec![4]);
+        assert_eq!(errors("(x y, { z })"), vec![3]);
+        // A missing `}` is reported once, not again after skipping the last statement
+        assert_eq!(errors("{ x = 1; y"), vec![10]);
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        let s = "(1, 2)";
+        assert_eq!(parse_bytes(s.as_bytes()), parse_expr(s));
+
+        assert_eq!(
+            parse_bytes(b"(1, \xff\xfe)"),
+            Err(ParseError::InvalidUtf8(4))
+        );
+        assert_eq!(parse_bytes(b"x\xc3"), Err(ParseError::InvalidUtf8(1)));
+    }
+
     #[test]
     fn test_efn() {
         let s = "x y z -> f(x, y)";
@@ -446,6 +1060,46 @@ mod test {
         assert_eq!(efn(span), Ok((Span::end(s), expr)),);
     }
 
+    #[test]
+    fn test_efn_missing_body() {
+        let s = "x ->";
+        assert_eq!(
+            parse_expr(s),
+            Err(ParseError::Expected("expression after '->'", Span::end(s)))
+        );
+        let s = "(x y -> )";
+        assert_eq!(
+            parse_expr(s),
+            Err(ParseError::Expected(
+                "expression after '->'",
+                Span::new(s, 8, 9)
+            ))
+        );
+        let s = "x -> ;";
+        assert_eq!(
+            parse_expr(s),
+            Err(ParseError::Expected(
+                "expression after '->'",
+                Span::new(s, 5, 6)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_efn_fallback() {
+        // Without an arrow, the ids are left for the other expression parsers
+        let s = "x";
+        assert_err!(efn(Span::from(s)));
+        assert_eq!(parse_expr(s), Ok(Expr::Id(Span::from(s))));
+        let s = "f(x)";
+        assert!(matches!(parse_expr(s), Ok(Expr::App(_))));
+        let s = "x y";
+        assert_eq!(
+            parse_expr(s),
+            Err(ParseError::Unexpected(Span::new(s, 2, 3)))
+        );
+    }
+
     #[test]
     fn test_eapp() {
         let s = "f(x, y)(z)";
@@ -491,9 +1145,202 @@ mod test {
                         pattern: Pattern::Id(Span::new(s, 10, 11)),
                         expr: Expr::Id(Span::new(s, 14, 15)),
                     },],
+                    default: None,
+                }),
+            )),
+        );
+    }
+
+    #[test]
+    fn test_ecase_default() {
+        let s = "case x of 1 = x of else = 2 end";
+        assert_eq!(
+            ecase(Span::from(s)),
+            Ok((
+                Span::end(s),
+                Expr::Case(Case {
+                    span: Span::from(s),
+                    subject: Box::new(Expr::Id(Span::new(s, 5, 6))),
+                    arms: vec![Arm {
+                        span: Span::new(s, 7, 15),
+                        pattern: Pattern::Int(Span::new(s, 10, 11)),
+                        expr: Expr::Id(Span::new(s, 14, 15)),
+                    },],
+                    default: Some(Box::new(DefaultArm {
+                        span: Span::new(s, 16, 27),
+                        expr: Expr::Int(Span::new(s, 26, 27)),
+                    })),
+                }),
+            )),
+        );
+
+        assert_err!(ecase(Span::from("case x of else = 1 of 2 = 3 end")));
+        assert_err!(ecase(Span::from("case x of else = 1 of else = 2 end")));
+    }
+
+    #[test]
+    fn test_statement_backtracks() {
+        // `f(x)` is also a pattern, so `assign` gets as far as the missing `=` before failing
+        let s = "f(x); y";
+        assert_eq!(
+            statement(Span::from(s)),
+            Ok((
+                Span::new(s, 4, 7),
+                Statement::Expr(Expr::App(App {
+                    span: Span::new(s, 0, 4),
+                    inner: Box::new(Expr::Id(Span::new(s, 0, 1))),
+                    arg_span: Span::new(s, 1, 4),
+                    args: vec![Expr::Id(Span::new(s, 2, 3))],
+                })),
+            )),
+        );
+
+        for s in ["f(x)(y)", "x, (y, z)", ":some(x)", "f(1_000)"] {
+            assert!(
+                matches!(statement(Span::from(s)), Ok((rest, Statement::Expr(_))) if rest.input_len() == 0),
+                "{s}"
+            );
+        }
+        // An annotation without `=` is not an assignment either
+        let s = "f(x) : T";
+        assert!(matches!(
+            statement(Span::from(s)),
+            Ok((rest, Statement::Expr(_))) if rest == Span::new(s, 4, 8)
+        ));
+
+        let s = "{ f(x); (a, b) = (1, 2); f(a) }";
+        let Ok(Expr::Do(do_struct)) = parse_expr(s) else {
+            panic!()
+        };
+        assert!(matches!(
+            &do_struct.statements[..],
+            [Statement::Expr(Expr::App(_)), Statement::Assign(_)]
+        ));
+    }
+
+    #[test]
+    fn test_edo_expr_statements() {
+        let s = "{ print; x }";
+        assert_eq!(
+            edo(Span::from(s)),
+            Ok((
+                Span::end(s),
+                Expr::Do(Do {
+                    span: Span::from(s),
+                    statements: vec![Statement::Expr(Expr::Id(Span::new(s, 2, 7)))],
+                    ret: Some(Box::new(Expr::Id(Span::new(s, 9, 10)))),
+                }),
+            )),
+        );
+
+        // The pattern parsed by `assign` must not swallow a lone call or literal
+        for (s, statement) in [
+            ("{ print(x); x }", "print(x)"),
+            ("{ 1234; x }", "1234"),
+            ("{ :tag; x }", ":tag"),
+            ("{ (); x }", "()"),
+        ] {
+            let Ok((_, Expr::Do(do_struct))) = edo(Span::from(s)) else {
+                panic!("{s}")
+            };
+            let [Statement::Expr(e)] = &do_struct.statements[..] else {
+                panic!("{s}: {:?}", do_struct.statements)
+            };
+            assert_eq!(e.span().as_inner(), statement);
+        }
+    }
+
+    #[test]
+    fn test_ecase_without_subject() {
+        // There is no implicit subject, as `case` on unit would only match `()` and `_`
+        let expected = "subject expression after 'case'";
+        let s = "case of :a = 1 end";
+        assert_eq!(
+            parse_expr(s),
+            Err(ParseError::Expected(expected, Span::new(s, 5, 18)))
+        );
+        let s = "f(case # nothing\n end)";
+        assert_eq!(
+            parse_expr(s),
+            Err(ParseError::Expected(expected, Span::new(s, 18, 22)))
+        );
+        let s = "case";
+        assert_eq!(
+            parse_expr(s),
+            Err(ParseError::Expected(expected, Span::end(s)))
+        );
+    }
+
+    #[test]
+    fn test_ws() {
+        let s = "  # one\n\t# two\n x";
+        assert_eq!(
+            ws(Span::from(s)),
+            Ok((Span::new(s, 16, 17), Span::new(s, 0, 16)))
+        );
+        let s = "# to the end";
+        assert_eq!(ws(Span::from(s)), Ok((Span::end(s), Span::from(s))));
+        let s = "x";
+        assert_eq!(ws(Span::from(s)), Ok((Span::from(s), Span::new(s, 0, 0))));
+    }
+
+    #[test]
+    fn test_ecase_comments() {
+        /// The tree of `s`, which must be the same with and without comments
+        fn same(s: &str) {
+            let plain = "case x of (a, b) = a of 1 = 2 of else = 3 end";
+            assert_eq!(
+                format_expr(&parse_expr(s).unwrap()),
+                format_expr(&parse_expr(plain).unwrap()),
+            );
+        }
+
+        same("case # subject\n x of (a, b) = a of 1 = 2 of else = 3 end");
+        same("case x # first\n of (a, b) = a # second\n of 1 = 2 of else = 3 end");
+        same("case x of # pattern\n (a, b) # equals\n = a of 1 = 2 of else = 3 end");
+        same("case x of (a, b) = # body\n a of 1 # one\n= 2 of else = 3 end");
+        same("case x of (a, # b\n b) = a of 1 = 2 of # default\n else # equals\n = 3 end");
+        same("case x of (a, b) = a of 1 = 2 of else = 3 # before end\n end");
+        same("# leading\ncase x of (a, b) = a of 1 = 2 of else = 3 end # trailing");
+    }
+
+    #[test]
+    fn test_assign() {
+        let s = "x = 1";
+        assert_eq!(
+            assign(Span::from(s)),
+            Ok((
+                Span::end(s),
+                Statement::Assign(Assign {
+                    span: Span::from(s),
+                    pattern: Pattern::Id(Span::new(s, 0, 1)),
+                    ty: None,
+                    expr: Expr::Int(Span::new(s, 4, 5)),
                 }),
             )),
         );
+
+        let s = "x : Int = 1";
+        assert_eq!(
+            assign(Span::from(s)),
+            Ok((
+                Span::end(s),
+                Statement::Assign(Assign {
+                    span: Span::from(s),
+                    pattern: Pattern::Id(Span::new(s, 0, 1)),
+                    ty: Some(Span::new(s, 4, 7)),
+                    expr: Expr::Int(Span::new(s, 10, 11)),
+                }),
+            )),
+        );
+
+        let s = "(x, y): Tuple = (1, 2)";
+        let Ok((_, Statement::Assign(a))) = assign(Span::from(s)) else {
+            panic!()
+        };
+        assert_eq!(a.ty, Some(Span::new(s, 8, 13)));
+
+        assert_err!(assign(Span::from("x : = 1")));
     }
 
     #[test]
@@ -574,6 +1421,121 @@ mod test {
         assert_eq!(pparen(span), Ok((Span::end(s), pat)),);
     }
 
+    #[test]
+    fn test_papp_nullary() {
+        let s = ":none";
+        assert_eq!(
+            papp(Span::from(s)),
+            Ok((
+                Span::end(s),
+                Pattern::Tag(Span::from(s), Span::new(s, 1, 5))
+            )),
+        );
+
+        // The empty argument list is not part of the pattern
+        let s = ":none()";
+        assert_eq!(
+            papp(Span::from(s)),
+            Ok((
+                Span::new(s, 5, 7),
+                Pattern::Tag(Span::new(s, 0, 5), Span::new(s, 1, 5))
+            )),
+        );
+        assert_err!(parse_expr("case x of :none() = 1 end"));
+        assert!(parse_expr("case x of :none = 1 end").is_ok());
+    }
+
+    #[test]
+    fn test_parse_pattern() {
+        let s = " (x, ..rest, y) ";
+        assert_eq!(
+            parse_pattern(s),
+            Ok(Pattern::Paren(
+                Span::new(s, 1, 15),
+                Box::new(Pattern::Tuple(
+                    Span::new(s, 2, 14),
+                    vec![
+                        Pattern::Id(Span::new(s, 2, 3)),
+                        Pattern::Collect(Ellipsis {
+                            span: Span::new(s, 5, 11),
+                            id: Some(Span::new(s, 7, 11)),
+                        }),
+                        Pattern::Id(Span::new(s, 13, 14)),
+                    ],
+                )),
+            )),
+        );
+
+        let s = ":some(a)";
+        assert_eq!(
+            parse_pattern(s),
+            Ok(Pattern::App(PatternApp {
+                span: Span::from(s),
+                f: Box::new(Pattern::Tag(Span::new(s, 0, 5), Span::new(s, 1, 5))),
+                arg_span: Span::new(s, 5, 8),
+                xs: vec![Pattern::Id(Span::new(s, 6, 7))],
+            })),
+        );
+
+        let s = ":some(a) = x";
+        assert_eq!(
+            parse_pattern(s),
+            Err(ParseError::Unexpected(Span::new(s, 9, 12)))
+        );
+        let s = "x y";
+        assert_eq!(
+            parse_pattern(s),
+            Err(ParseError::Unexpected(Span::new(s, 2, 3)))
+        );
+    }
+
+    #[test]
+    fn test_por() {
+        let s = ":a | :b";
+        assert_eq!(
+            parse_pattern(s),
+            Ok(Pattern::Or(
+                Span::from(s),
+                vec![
+                    Pattern::Tag(Span::new(s, 0, 2), Span::new(s, 1, 2)),
+                    Pattern::Tag(Span::new(s, 5, 7), Span::new(s, 6, 7)),
+                ],
+            )),
+        );
+
+        // Tuples bind more tightly than alternatives
+        let p = parse_pattern("x, 1 | x, 2 | (x, ..)").unwrap();
+        assert!(matches!(p, Pattern::Or(_, ref xs) if xs.len() == 3));
+        let s = "(:a | :b), x";
+        assert!(matches!(parse_pattern(s), Ok(Pattern::Tuple(..))));
+        assert!(matches!(
+            parse_expr("case x of :some(y) | :ok(y) = y end"),
+            Ok(Expr::Case(_))
+        ));
+
+        let s = "(1, x) | (2, y)";
+        assert_eq!(
+            parse_pattern(s),
+            Err(ParseError::Expected(
+                "the same names as the first alternative",
+                Span::new(s, 9, 15)
+            ))
+        );
+        let s = ":some(x) | :none";
+        assert_eq!(
+            parse_pattern(s),
+            Err(ParseError::Expected(
+                "the same names as the first alternative",
+                Span::new(s, 11, 16)
+            ))
+        );
+        let s = "x | ";
+        assert_eq!(
+            parse_pattern(s),
+            Err(ParseError::Expected("pattern after '|'", Span::end(s)))
+        );
+    }
+
     #[test]
     fn test_papp() {
         let s = "f(x, y)(z)";
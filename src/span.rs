@@ -3,25 +3,39 @@ use nom::{
     Compare, Err, IResult, InputIter, InputLength, InputTake, InputTakeAtPosition, Offset, Slice,
 };
 use std::ops::{Range, RangeFrom, RangeFull, RangeTo};
-use unwrap::unwrap;
 
+/// A half-open range `start..end` of byte offsets into `inner`, which is always the whole source
+///
+/// Every constructor requires `start <= end <= inner.input_len()`, checked in debug builds. In
+/// release builds a span breaking this is replaced by an empty one, so that slicing the source by a
+/// span never panics.
 #[derive(Clone, Copy, PartialEq, Eq)]
-pub(crate) struct Span<T> {
+pub struct Span<T> {
     inner: T,
     start: usize,
     end: usize,
 }
 
-impl<'a> Span<&'a str> {
-    pub(crate) fn value_i64(&self) -> i64 {
-        unwrap!(
-            self.as_inner().parse::<i64>(),
-            "interpreter: {:?} failed to parse to i64",
-            self
-        )
+impl Span<&str> {
+    /// 1-based line and column of the start of the span, counting columns in chars
+    pub(crate) fn line_col(&self) -> (usize, usize) {
+        line_col(self.inner, self.start)
+    }
+
+    /// 1-based line and column of the end of the span, counting columns in chars
+    pub(crate) fn end_line_col(&self) -> (usize, usize) {
+        line_col(self.inner, self.end)
     }
 }
 
+fn line_col(s: &str, offset: usize) -> (usize, usize) {
+    let before = &s[..offset.min(s.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let col = before[line_start..].chars().count() + 1;
+    (line, col)
+}
+
 // impl<T> std::fmt::Debug for Span<T> {
 //     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 //         fmt.debug_tuple("Span")
@@ -33,7 +47,7 @@ impl<'a> Span<&'a str> {
 
 impl<T> std::fmt::Debug for Span<T>
 where
-    T: std::fmt::Debug + Slice<Range<usize>>,
+    T: std::fmt::Debug + Slice<Range<usize>> + InputLength,
 {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         fmt.debug_tuple("Span").field(&self.as_inner()).finish()
@@ -41,12 +55,24 @@ where
 }
 
 impl<T> Span<T> {
-    pub(crate) fn new(inner: T, start: usize, end: usize) -> Self {
+    /// The span covering `start..end` of `inner`
+    pub fn new(inner: T, start: usize, end: usize) -> Self
+    where
+        T: InputLength,
+    {
+        debug_assert!(start <= end, "span: start ({start}) is after end ({end})");
+        debug_assert!(
+            end <= inner.input_len(),
+            "span: end ({end}) is past the end of the source ({})",
+            inner.input_len()
+        );
+        let (start, end) = clamp(start, end, inner.input_len());
         Self { inner, start, end }
     }
 
+    /// The empty span at the end of `inner`
     #[allow(dead_code)]
-    pub(crate) fn end(inner: T) -> Self
+    pub fn end(inner: T) -> Self
     where
         T: InputLength,
     {
@@ -54,28 +80,89 @@ impl<T> Span<T> {
         Span::new(inner, length, length)
     }
 
-    pub(crate) fn as_inner(&self) -> T
+    pub fn as_inner(&self) -> T
     where
-        T: Slice<Range<usize>>,
+        T: Slice<Range<usize>> + InputLength,
     {
-        self.inner.slice(self.start..self.end)
+        let (start, end) = clamp(self.start, self.end, self.inner.input_len());
+        self.inner.slice(start..end)
     }
 
+    /// The span from the start of `first` up to, but excluding, the start of `second`
+    ///
+    /// This is typically used with the input before and after a parser, to get the span it
+    /// consumed.
     pub(crate) fn between(first: Span<T>, second: Span<T>) -> Self
     where
-        T: Clone,
+        T: Clone + InputLength,
     {
+        debug_assert!(
+            first.start <= second.start,
+            "span: between second span ({}) starts before first span ({})",
+            second.start,
+            first.start
+        );
         Span::new(first.inner.clone(), first.start, second.start)
     }
 
+    /// The span from the start of `first` to the end of `second`, inclusive of both
     pub(crate) fn to(first: Span<T>, second: Span<T>) -> Self
     where
-        T: Clone,
+        T: Clone + InputLength,
     {
+        debug_assert!(
+            first.start <= second.end,
+            "span: to second span ({}) ends before first span ({})",
+            second.end,
+            first.start
+        );
         Self::new(first.inner.clone(), first.start, second.end)
     }
+
+    /// The span from the start of the first span to the end of the last, or `None` if there are
+    /// no spans
+    ///
+    /// The spans are expected in source order, as the children of a node are.
+    #[allow(dead_code)]
+    pub(crate) fn join_all(spans: impl IntoIterator<Item = Span<T>>) -> Option<Self>
+    where
+        T: Clone + InputLength,
+    {
+        let mut spans = spans.into_iter();
+        let first = spans.next()?;
+        let last = spans.last().unwrap_or_else(|| first.clone());
+        Some(Span::to(first, last))
+    }
+}
+
+/// Concise span construction for tests: `span!(s)` covers all of `s`, and `span!(s, a..b)` covers
+/// the bytes `a..b` of `s`
+#[cfg(test)]
+macro_rules! span {
+    ($s: expr) => {
+        $crate::span::Span::from($s)
+    };
+    ($s: expr, $range: expr) => {{
+        let range: std::ops::Range<usize> = $range;
+        $crate::span::Span::new($s, range.start, range.end)
+    }};
 }
 
+#[cfg(test)]
+pub(crate) use span;
+
+/// `start..end` if it is a valid range into a source of length `len`, or else an empty range at
+/// the nearest valid offset to `start`
+fn clamp(start: usize, end: usize, len: usize) -> (usize, usize) {
+    if start <= end && end <= len {
+        (start, end)
+    } else {
+        let start = start.min(len);
+        (start, start)
+    }
+}
+
+/// The span covering all of `inner`
 impl<T> From<T> for Span<T>
 where
     T: InputLength,
@@ -91,15 +178,14 @@ where
     T: InputLength,
 {
     fn input_len(&self) -> usize {
-        self.end
-            .min(self.inner.input_len())
-            .saturating_sub(self.start)
+        let (start, end) = clamp(self.start, self.end, self.inner.input_len());
+        end - start
     }
 }
 
 impl<T> Slice<Range<usize>> for Span<T>
 where
-    T: Clone,
+    T: Clone + InputLength,
 {
     fn slice(&self, range: Range<usize>) -> Self {
         let start = self.start + range.start;
@@ -110,7 +196,7 @@ where
 
 impl<T> Slice<RangeFrom<usize>> for Span<T>
 where
-    T: Clone,
+    T: Clone + InputLength,
 {
     fn slice(&self, range: RangeFrom<usize>) -> Self {
         let start = self.start + range.start;
@@ -121,7 +207,7 @@ where
 
 impl<T> Slice<RangeTo<usize>> for Span<T>
 where
-    T: Clone,
+    T: Clone + InputLength,
 {
     fn slice(&self, range: RangeTo<usize>) -> Self {
         let start = self.start;
@@ -141,7 +227,7 @@ where
 
 impl<T> InputTake for Span<T>
 where
-    T: Clone,
+    T: Clone + InputLength,
 {
     /// We're taking bytes, so we use AsBytes
     fn take(&self, count: usize) -> Self {
@@ -225,7 +311,7 @@ where
 
 impl<T> InputIter for Span<T>
 where
-    T: InputIter + Slice<Range<usize>>,
+    T: InputIter + InputLength + Slice<Range<usize>>,
 {
     type Item = <T as InputIter>::Item;
     type Iter = <T as InputIter>::Iter;
@@ -253,7 +339,7 @@ where
 
 impl<T, U> Compare<U> for Span<T>
 where
-    T: Compare<U> + Slice<Range<usize>>,
+    T: Compare<U> + InputLength + Slice<Range<usize>>,
 {
     fn compare(&self, t: U) -> nom::CompareResult {
         self.as_inner().compare(t)
@@ -330,4 +416,131 @@ mod test {
             )),
         );
     }
+
+    #[test]
+    fn test_span_macro() {
+        let s = "hello world";
+        assert_eq!(span!(s), Span::new(s, 0, 11));
+        assert_eq!(span!(s, 6..11), Span::new(s, 6, 11));
+        assert_eq!(span!(s, 6..11).as_inner(), "world");
+        assert_eq!(span!(s, 11..11), Span::end(s));
+    }
+
+    #[test]
+    fn test_span_macro_parse() {
+        let s = "thinghello";
+
+        fn parse(s: Span<&str>) -> IResult<Span<&str>, (Span<&str>, Span<&str>)> {
+            pair(tag("thing"), alpha1)(s)
+        }
+
+        assert_eq!(
+            parse(span!(s)),
+            Ok((span!(s, 10..10), (span!(s, 0..5), span!(s, 5..10)))),
+        );
+    }
+
+    #[test]
+    fn test_between_to() {
+        let s = "hello world";
+        assert_eq!(
+            Span::between(span!(s, 0..5), span!(s, 6..11)),
+            span!(s, 0..6)
+        );
+        assert_eq!(Span::to(span!(s, 0..5), span!(s, 6..11)), span!(s, 0..11));
+        assert_eq!(
+            Span::between(span!(s, 3..5), span!(s, 3..4)),
+            span!(s, 3..3)
+        );
+    }
+
+    #[test]
+    fn test_join_all() {
+        let s = "f(x, yy, z)";
+        assert_eq!(
+            Span::join_all([span!(s, 2..3), span!(s, 5..7), span!(s, 9..10)]),
+            Some(span!(s, 2..10))
+        );
+        assert_eq!(
+            Span::join_all(vec![span!(s, 5..7), span!(s, 9..10)]),
+            Some(span!(s, 5..10))
+        );
+        assert_eq!(Span::join_all([span!(s, 5..7)]), Some(span!(s, 5..7)));
+        assert_eq!(Span::join_all(Vec::<Span<&str>>::new()), None);
+    }
+
+    #[test]
+    fn test_degenerate_spans() {
+        // Built directly, as `Span::new` rejects these in debug builds
+        let reversed = Span {
+            inner: "hello",
+            start: 3,
+            end: 2,
+        };
+        let past_end = Span {
+            inner: "hello",
+            start: 2,
+            end: 9,
+        };
+        let outside = Span {
+            inner: "hello",
+            start: 7,
+            end: 9,
+        };
+        for span in [reversed, past_end, outside] {
+            assert_eq!(span.as_inner(), "");
+            assert_eq!(span.input_len(), 0);
+            assert_eq!(format!("{span:?}"), "Span(\"\")");
+            span.line_col();
+            span.end_line_col();
+        }
+    }
+
+    #[test]
+    fn test_clamp() {
+        assert_eq!(clamp(1, 3, 5), (1, 3));
+        assert_eq!(clamp(5, 5, 5), (5, 5));
+        assert_eq!(clamp(3, 2, 5), (3, 3));
+        assert_eq!(clamp(2, 9, 5), (2, 2));
+        assert_eq!(clamp(7, 9, 5), (5, 5));
+        assert_eq!(clamp(usize::MAX, 0, 5), (5, 5));
+    }
+
+    #[test]
+    #[cfg(not(debug_assertions))]
+    fn test_new_degenerate_release() {
+        assert_eq!(Span::new("hello", 3, 2), span!("hello", 3..3));
+        assert_eq!(Span::new("hello", 2, 9).as_inner(), "");
+        assert_eq!(Span::new("hello", 7, 9), Span::end("hello"));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "is after end")]
+    fn test_new_reversed() {
+        Span::new("hello", 3, 2);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "past the end of the source")]
+    fn test_new_out_of_bounds() {
+        Span::new("hello", 2, 6);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "starts before first span")]
+    fn test_between_reversed() {
+        let s = "hello world";
+        Span::between(span!(s, 6..11), span!(s, 0..5));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "ends before first span")]
+    fn test_to_reversed() {
+        let s = "hello world";
+        Span::to(span!(s, 6..11), span!(s, 0..5));
+    }
 }
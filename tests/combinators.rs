@@ -0,0 +1,41 @@
+use fast_rs::{
+    expr::{Input, Pattern, Statement},
+    parse::combinators::{expr, pattern, statement, ws, IResult},
+    span::Span,
+};
+use nom::{
+    bytes::complete::tag,
+    character::complete::multispace1,
+    sequence::{pair, preceded, separated_pair, tuple},
+};
+
+#[test]
+fn test_trailing_whitespace() {
+    for s in ["x ", "x, ", "x, y ", "f(x) ", "x -> x, "] {
+        let (rest, _) = expr(Span::from(s)).unwrap();
+        assert_eq!(rest.as_inner(), " ", "{s:?}");
+        let (rest, _) = statement(Span::from(s)).unwrap();
+        assert_eq!(rest.as_inner(), " ", "{s:?}");
+    }
+    for s in ["x ", "x, ", "x, ..y ", "f(x) "] {
+        let (rest, _) = pattern(Span::from(s)).unwrap();
+        assert_eq!(rest.as_inner(), " ", "{s:?}");
+    }
+}
+
+#[test]
+fn test_host_binding() {
+    /// `let pattern := statement`, reusing the pattern and statement grammars
+    fn binding(s: Input) -> IResult<Input, (Pattern, Statement)> {
+        preceded(
+            pair(tag("let"), multispace1),
+            separated_pair(pattern, tuple((ws, tag(":="), ws)), statement),
+        )(s)
+    }
+
+    let s = "let x, y := z = (1, 2)";
+    let (rest, (p, st)) = binding(Span::from(s)).unwrap();
+    assert_eq!(rest, Span::end(s));
+    assert!(matches!(p, Pattern::Tuple(_, ref xs) if xs.len() == 2));
+    assert!(matches!(st, Statement::Assign(_)));
+}